@@ -1,55 +1,101 @@
 /*
     ABox axioms have the following format:
     "C[x]", "r[x, y]", "(some r C)[x]", etc.
+    Equality and inequality of individuals are stated as "sameAs[x, y]" and "differentFrom[x, y]",
+    negated relations as "not r[x, y]".
     This makes parsing easy without the loss of readability
 */
 use std::fmt;
 use std::hash;
 use std::clone::Clone;
-use std::string;
 use std::collections::{HashSet, HashMap};
 use std::iter::FromIterator;
 
-use concept::{Individual, Relation, Concept, AtomicConcept, ConceptType, parse_concept};
+use concept::{Individual, Relation, Concept, TopConcept, NominalConcept, ConceptType, parse_concept_in};
+use error::ParseError;
 
 
-pub fn parse_abox(abox_str: &str) -> ABox {
-    let abox_str = abox_str.trim();
+pub fn parse_abox(abox_str: &str) -> Result<ABox, Vec<ParseError>> {
+    // Parses the whole ABox, collecting errors from all the lines instead of stopping at the first one
     let mut abox = ABox::new();
+    let mut errors = vec![];
 
-    for line in abox_str.lines() {
+    for (i, line) in abox_str.lines().enumerate() {
         debug!("Parsing line: {}", line);
-        if line.len() > 0 && !line.starts_with('#') {
-            add_abox_axiom(&mut abox, &line);
+        let trimmed_line = line.trim();
+
+        if !trimmed_line.is_empty() && !trimmed_line.starts_with('#') {
+            if let Err(e) = add_abox_axiom(&mut abox, line) {
+                errors.push(e.at_line(i + 1));
+            }
         }
     }
 
-    abox
+    if errors.is_empty() { Ok(abox) } else { Err(errors) }
 }
 
 
-pub fn add_abox_axiom(abox: &mut ABox, axiom_str: &str) {
-    let axiom_str = axiom_str.trim();
-    let start_idx = axiom_str.find("[").unwrap_or(0);
-    let end_idx = axiom_str.find("]").unwrap_or(axiom_str.len());
-    let arguments_str = &axiom_str[start_idx+1..end_idx].trim();
+pub fn add_abox_axiom(abox: &mut ABox, axiom_line: &str) -> Result<(), ParseError> {
+    let axiom_str = axiom_line.trim();
+    let start_idx = axiom_str.find('[')
+        .ok_or_else(|| ParseError::new(axiom_line, &axiom_str[axiom_str.len()..], "`[` with individuals, like in `C[x]` or `r[x, y]`"))?;
+    let end_idx = axiom_str[start_idx..].find(']').map(|i| start_idx + i)
+        .ok_or_else(|| ParseError::new(axiom_line, &axiom_str[axiom_str.len()..], "`]`"))?;
+
+    if end_idx + 1 != axiom_str.len() {
+        return Err(ParseError::new(axiom_line, axiom_str[end_idx + 1..].trim(), "the end of the axiom"));
+    }
+
+    let arguments_str = &axiom_str[start_idx+1..end_idx];
     debug!("arguments string: {}", arguments_str);
-    let individuals = arguments_str
-        .chars().filter(|c| !c.is_whitespace()).collect::<String>()
-        .split(',').map(|n| (Individual {name: n.to_string()}))
+    let arguments = arguments_str.split(',').map(|n| n.trim()).collect::<Vec<&str>>();
+
+    if let Some(bad_name) = arguments.iter().find(|n| n.is_empty() || n.contains(char::is_whitespace)) {
+        return Err(ParseError::new(axiom_line, bad_name, "an individual name"));
+    } else if arguments.len() > 2 {
+        return Err(ParseError::new(axiom_line, arguments[2], "`]` (axioms take one or two individuals)"));
+    }
+
+    let individuals = arguments.iter()
+        .map(|n| Individual {name: n.to_string()})
         .collect::<Vec<_>>();
+    let head_str = axiom_str[..start_idx].trim();
 
-    if arguments_str.contains(",") {
-        // This is a relation axiom
-        abox.axioms.insert(Box::new(RelationAxiom {
-            relation: Relation { name: axiom_str[..start_idx].to_string() },
-            lhs: individuals[0].clone(),
-            rhs: individuals[1].clone()
+    if individuals.len() == 2 && head_str == "sameAs" {
+        // x = y is the same as x: {y}, so the nominal rule will merge them
+        abox.axioms.insert(Box::new(ConceptAxiom {
+            concept: Box::new(NominalConcept { individuals: vec![individuals[1].clone()] }),
+            individual: individuals[0].clone()
         }));
+    } else if individuals.len() == 2 && head_str == "differentFrom" {
+        if individuals[0] == individuals[1] {
+            abox.is_consistent = Some(false);
+        }
+
+        abox.pairwise_different_individuals.push(HashSet::from_iter(individuals.iter().cloned()));
+    } else if individuals.len() == 2 {
+        // This is a relation axiom (or a negated one)
+        let relation_str = match head_str.strip_prefix("not") {
+            Some(rest) if rest.starts_with(char::is_whitespace) => rest.trim(),
+            _ => head_str
+        };
+
+        if relation_str.is_empty() || relation_str == "-" || relation_str.contains(|c: char| c.is_whitespace() || c == '(' || c == ')') {
+            return Err(ParseError::new(axiom_line, relation_str, "a relation name"));
+        }
+
+        let relation = Relation::new(relation_str);
+        let (lhs, rhs) = (individuals[0].clone(), individuals[1].clone());
+
+        if relation_str.len() == head_str.len() {
+            abox.axioms.insert(Box::new(RelationAxiom { relation, lhs, rhs }));
+        } else {
+            abox.axioms.insert(Box::new(NegativeRelationAxiom { relation, lhs, rhs }));
+        }
     } else {
         // This is a concept axiom
         abox.axioms.insert(Box::new(ConceptAxiom {
-            concept: parse_concept(&axiom_str[..start_idx]).convert_to_nnf(),
+            concept: parse_concept_in(head_str, axiom_line)?.convert_to_nnf(),
             individual: individuals[0].clone()
         }));
     }
@@ -57,11 +103,13 @@ pub fn add_abox_axiom(abox: &mut ABox, axiom_str: &str) {
     for x in individuals {
         abox.add_individual(x);
     }
+
+    Ok(())
 }
 
 
 #[derive(PartialEq)]
-pub enum ABoxAxiomType { Concept, Relation }
+pub enum ABoxAxiomType { Concept, Relation, NegativeRelation }
 
 #[derive(Debug, Clone)]
 pub struct ABox {
@@ -70,7 +118,15 @@ pub struct ABox {
     pub is_complete: Option<bool>,
     pub individuals: HashSet<Individual>,
     pub pairwise_different_individuals: Vec<HashSet<Individual>>,
-    pub replacements: HashMap<Individual, Individual>
+    pub replacements: HashMap<Individual, Individual>,
+    // Under the unique name assumption named individuals can never be merged
+    pub unique_name_assumption: bool
+}
+
+impl Default for ABox {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ABox {
@@ -81,7 +137,8 @@ impl ABox {
             is_complete: None,
             individuals: HashSet::new(),
             pairwise_different_individuals: vec![],
-            replacements: HashMap::new()
+            replacements: HashMap::new(),
+            unique_name_assumption: false
         }
     }
 
@@ -91,31 +148,25 @@ impl ABox {
         model.individuals = self.individuals.clone().into_iter().collect::<Vec<Individual>>();
         model.relation_axioms = self.axioms.clone().into_iter()
             .filter(|a| a.axiom_type() == ABoxAxiomType::Relation)
-            .map(|a| a.downcast_ref::<RelationAxiom>().unwrap().clone())
-            .map(|a| a.clone())
+            .map(|a| a.downcast_ref::<RelationAxiom>().unwrap().normalized())
             .collect::<Vec<RelationAxiom>>();
+        model.negative_relation_axioms = self.axioms.iter()
+            .filter_map(|a| a.downcast_ref::<NegativeRelationAxiom>())
+            .map(|a| a.normalized())
+            .collect::<Vec<NegativeRelationAxiom>>();
         model.concept_axioms = self.axioms.clone().into_iter()
             .filter(|a| a.axiom_type() == ABoxAxiomType::Concept)
             .map(|a| a.downcast_ref::<ConceptAxiom>().unwrap().clone())
             .filter(|a| a.concept.concept_type() == ConceptType::Atomic)
-            .filter(|a| a.concept.downcast_ref::<AtomicConcept>().unwrap().name != "__TOP__")
-            .map(|a| a.clone())
             .collect::<Vec<ConceptAxiom>>();
         model.replacements = self.replacements.clone();
 
         model
     }
 
-    // pub fn create_new_individual(&mut self) -> Individual {
-    //     let new_x = Individual {name: format!("x_#{}", abox.individuals.len())};
-
-    //     self.individuals.insert(new_x.clone());
-    //     self.add_top_axiom_for_individual(new_x);
-    // }
-
     pub fn add_top_axiom_for_individual(&mut self, x: Individual) {
         self.axioms.insert(Box::new(ConceptAxiom {
-            concept: Box::new(AtomicConcept {name: "__TOP__".to_string()}) as Box<dyn Concept>,
+            concept: Box::new(TopConcept) as Box<dyn Concept>,
             individual: x
         }) as Box<dyn ABoxAxiom>);
     }
@@ -138,10 +189,17 @@ impl fmt::Display for ABox {
 
 
 pub struct Model {
-    individuals: Vec<Individual>,
-    concept_axioms: Vec<ConceptAxiom>,
-    relation_axioms: Vec<RelationAxiom>,
-    replacements: HashMap<Individual, Individual>
+    pub individuals: Vec<Individual>,
+    pub concept_axioms: Vec<ConceptAxiom>,
+    pub relation_axioms: Vec<RelationAxiom>,
+    pub negative_relation_axioms: Vec<NegativeRelationAxiom>,
+    pub replacements: HashMap<Individual, Individual>
+}
+
+impl Default for Model {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Model {
@@ -150,6 +208,7 @@ impl Model {
             individuals: vec![],
             concept_axioms: vec![],
             relation_axioms: vec![],
+            negative_relation_axioms: vec![],
             replacements: HashMap::new()
         }
     }
@@ -163,11 +222,13 @@ impl fmt::Display for Model {
             .map(|c| c.to_string()).collect::<Vec<String>>().join(", "));
         let relations = format!("Relations: {}", self.relation_axioms.iter()
             .map(|r| r.to_string()).collect::<Vec<String>>().join(", "));
+        let negative_relations = format!("Negative relations: {}", self.negative_relation_axioms.iter()
+            .map(|r| r.to_string()).collect::<Vec<String>>().join(", "));
         let replacements = format!("Replacements: {}", self.replacements.iter()
-            .map(|(x, y)| format!("{} = {}", x.to_string(), y.to_string()))
+            .map(|(x, y)| format!("{} = {}", x, y))
             .collect::<Vec<String>>().join(", "));
 
-        write!(fmt, "Model:\n - {}\n - {}\n - {}\n - {}", individuals, concepts, relations, replacements)
+        write!(fmt, "Model:\n - {}\n - {}\n - {}\n - {}\n - {}", individuals, concepts, relations, negative_relations, replacements)
     }
 }
 
@@ -226,9 +287,19 @@ pub struct RelationAxiom {
     pub rhs: Individual,
 }
 
+impl RelationAxiom {
+    pub fn normalized(&self) -> RelationAxiom {
+        // r-(x, y) is the same as r(y, x)
+        match self.relation.is_inverse {
+            true => RelationAxiom { relation: self.relation.inverse(), lhs: self.rhs.clone(), rhs: self.lhs.clone() },
+            false => self.clone()
+        }
+    }
+}
+
 impl fmt::Display for RelationAxiom {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        write!(fmt, "{}({}, {})", self.relation.name, self.lhs.name, self.rhs.name)
+        write!(fmt, "{}({}, {})", self.relation, self.lhs.name, self.rhs.name)
     }
 }
 
@@ -241,3 +312,29 @@ impl fmt::Display for RelationAxiom {
 impl ABoxAxiom for RelationAxiom {
     fn axiom_type(&self) -> ABoxAxiomType { ABoxAxiomType::Relation }
 }
+
+#[derive(Debug, Clone, Hash)]
+pub struct NegativeRelationAxiom {
+    pub relation: Relation,
+    pub lhs: Individual,
+    pub rhs: Individual,
+}
+
+impl NegativeRelationAxiom {
+    pub fn normalized(&self) -> NegativeRelationAxiom {
+        match self.relation.is_inverse {
+            true => NegativeRelationAxiom { relation: self.relation.inverse(), lhs: self.rhs.clone(), rhs: self.lhs.clone() },
+            false => self.clone()
+        }
+    }
+}
+
+impl fmt::Display for NegativeRelationAxiom {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "not {}({}, {})", self.relation, self.lhs.name, self.rhs.name)
+    }
+}
+
+impl ABoxAxiom for NegativeRelationAxiom {
+    fn axiom_type(&self) -> ABoxAxiomType { ABoxAxiomType::NegativeRelation }
+}
@@ -0,0 +1,656 @@
+/*
+    Completion graph is the structure, which the tableau expands.
+    Every node (individual) has a label (the concepts it belongs to) and every edge has a set of relations,
+    so that the rules do not have to scan the whole ABox to find a label or the neighbours of an individual.
+    All the changes are recorded on a trail: on backtracking we undo them up to a branch point
+    instead of keeping a copy of the whole graph for every alternative.
+    Every concept, edge and merge remembers the branch points it depends on (for the backjumping).
+    Labels contain the ids of the concepts interned by the graph's concept factory.
+    A node can be cached: its label is known to be satisfiable, so the rules do not expand it.
+    Every added concept is put on the todo queue of the rule it triggers, so a rule looks only at its own concepts,
+    in the order they were added (which makes the expansion deterministic). The entries before the head of a queue are done.
+*/
+use std::fmt;
+use std::collections::{HashSet, HashMap, BTreeSet};
+use std::iter::FromIterator;
+
+use concept::{Individual, Relation};
+use concept_factory::{ConceptFactory, ConceptId, ConceptNode};
+use abox::{ABox, ABoxAxiom, ABoxAxiomType, ConceptAxiom, RelationAxiom, NegativeRelationAxiom};
+
+
+// Branch points of the tableau (i.e. nondeterministic choices), which a fact depends on
+pub type DependencySet = BTreeSet<usize>;
+
+
+#[derive(Debug, Clone)]
+enum Change {
+    AddIndividual(Individual),
+    RemoveIndividual(Individual, usize, Node),
+    AddConcept(Individual, ConceptId),
+    RemoveConcept(Individual, ConceptId, DependencySet),
+    AddEdge(Individual, Individual, Relation),
+    RemoveEdge(Individual, Individual, Relation, DependencySet),
+    AddNegativeEdge(Individual, Individual, Relation),
+    RemoveNegativeEdge(Individual, Individual, Relation, DependencySet),
+    AddDifferentIndividuals,
+    UpdateDifferentIndividuals(usize, HashSet<Individual>),
+    AddReplacement(Individual),
+    SetCached(Individual),
+    SkipToDo(RuleType, usize),
+    SetClash
+}
+
+// The rules, which are triggered by the concepts of the labels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RuleType { Conjunction, Unfolding, Disjunction, Nominal, NegatedNominal, Only, Some, AtLeast, AtMost }
+
+#[derive(Debug, Clone, Default)]
+struct ToDoQueue {
+    // In the order, in which the concepts were added
+    entries: Vec<(Individual, ConceptId)>,
+    head: usize
+}
+
+#[derive(Debug, Clone, Default)]
+struct Node {
+    label: HashMap<ConceptId, DependencySet>,
+    // Individuals, which have an edge from or to this one
+    neighbours: HashSet<Individual>,
+    is_cached: bool
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CompletionGraph {
+    pub factory: ConceptFactory,
+    // In the order of creation
+    individuals: Vec<Individual>,
+    nodes: HashMap<Individual, Node>,
+    // Edges are directed as they were added, i.e. r(x, y) is stored under (x, y)
+    edges: HashMap<(Individual, Individual), HashMap<Relation, DependencySet>>,
+    negative_edges: HashMap<(Individual, Individual, Relation), DependencySet>,
+    different_individuals: Vec<(HashSet<Individual>, DependencySet)>,
+    replacements: HashMap<Individual, (Individual, DependencySet)>,
+    unique_name_assumption: bool,
+    clash: Option<DependencySet>,
+    todo: HashMap<RuleType, ToDoQueue>,
+    trail: Vec<Change>
+}
+
+impl CompletionGraph {
+    pub fn from_abox(abox: &ABox) -> CompletionGraph {
+        CompletionGraph::from_abox_with_factory(abox, ConceptFactory::new())
+    }
+
+    pub fn from_abox_with_factory(abox: &ABox, factory: ConceptFactory) -> CompletionGraph {
+        // The factory may already have some concepts interned (e.g. by the previous runs), their ids stay the same
+        let mut graph = CompletionGraph { factory, unique_name_assumption: abox.unique_name_assumption, ..Default::default() };
+        let no_dependencies = DependencySet::new();
+        let mut individuals = abox.individuals.iter().cloned().collect::<Vec<Individual>>();
+
+        // Named individuals go first, the generated ones are ordered by their age
+        individuals.sort_by(|x, y| x.is_younger(y).cmp(&y.is_younger(x)).then(x.name.cmp(&y.name)));
+
+        for x in individuals {
+            graph.add_individual(x);
+        }
+
+        // The axioms are sorted, so that the todo queues do not depend on the order of a hash set
+        let mut axioms = abox.axioms.iter().collect::<Vec<_>>();
+        axioms.sort_by_key(|a| a.to_string());
+
+        for axiom in axioms {
+            match axiom.axiom_type() {
+                ABoxAxiomType::Concept => {
+                    let a = axiom.downcast_ref::<ConceptAxiom>().unwrap();
+                    let concept = graph.factory.intern(&*a.concept);
+                    graph.add_concept(&a.individual, concept, &no_dependencies);
+                },
+                ABoxAxiomType::Relation => {
+                    let a = axiom.downcast_ref::<RelationAxiom>().unwrap();
+                    graph.add_edge(&a.lhs, &a.rhs, &a.relation, &no_dependencies);
+                },
+                ABoxAxiomType::NegativeRelation => {
+                    let a = axiom.downcast_ref::<NegativeRelationAxiom>().unwrap();
+                    graph.add_negative_edge(&a.lhs, &a.rhs, &a.relation, &no_dependencies);
+                }
+            }
+        }
+
+        for xs in &abox.pairwise_different_individuals {
+            graph.add_different_individuals(xs.clone(), &no_dependencies);
+        }
+
+        for (x, y) in &abox.replacements {
+            graph.replacements.insert(x.clone(), (y.clone(), DependencySet::new()));
+        }
+
+        if abox.is_consistent == Some(false) {
+            graph.set_clash(&no_dependencies);
+        }
+
+        // There is nothing to undo in the initial graph
+        graph.trail.clear();
+        graph
+    }
+
+    pub fn to_abox(&self) -> ABox {
+        let mut abox = ABox::new();
+
+        abox.individuals = HashSet::from_iter(self.individuals.iter().cloned());
+        abox.axioms = self.nodes.iter()
+            .flat_map(|(x, node)| node.label.keys().map(move |&c| Box::new(ConceptAxiom {
+                concept: self.factory.concept(c),
+                individual: x.clone()
+            }) as Box<dyn ABoxAxiom>))
+            .chain(self.edges.iter().flat_map(|((x, y), relations)| relations.keys().map(move |r| Box::new(RelationAxiom {
+                relation: r.clone(),
+                lhs: x.clone(),
+                rhs: y.clone()
+            }) as Box<dyn ABoxAxiom>)))
+            .chain(self.negative_edges.keys().map(|(x, y, r)| Box::new(NegativeRelationAxiom {
+                relation: r.clone(),
+                lhs: x.clone(),
+                rhs: y.clone()
+            }) as Box<dyn ABoxAxiom>))
+            .collect();
+        abox.pairwise_different_individuals = self.different_individuals.iter().map(|(xs, _)| xs.clone()).collect();
+        abox.replacements = self.replacements.iter().map(|(x, (y, _))| (x.clone(), y.clone())).collect();
+        abox.unique_name_assumption = self.unique_name_assumption;
+
+        if self.clash.is_some() {
+            abox.is_consistent = Some(false);
+        }
+
+        abox
+    }
+
+    pub fn individuals(&self) -> &[Individual] {
+        &self.individuals
+    }
+
+    pub fn contains_individual(&self, x: &Individual) -> bool {
+        self.nodes.contains_key(x)
+    }
+
+    pub fn add_individual(&mut self, x: Individual) {
+        debug_assert!(!self.contains_individual(&x));
+
+        self.individuals.push(x.clone());
+        self.nodes.insert(x.clone(), Node::default());
+        self.trail.push(Change::AddIndividual(x.clone()));
+
+        let top = self.factory.intern_node(ConceptNode::Top);
+        self.add_concept(&x, top, &DependencySet::new());
+    }
+
+    pub fn create_new_individual(&mut self) -> Individual {
+        // Merged individuals are kept in `replacements`, so we never reuse a name (unless we backtrack)
+        let new_x = Individual { name: format!("x_#{}", self.individuals.len() + self.replacements.len()) };
+        self.add_individual(new_x.clone());
+
+        new_x
+    }
+
+    pub fn label(&self, x: &Individual) -> Vec<ConceptId> {
+        // Sorted to keep the expansion deterministic
+        let mut label = self.nodes[x].label.keys().cloned().collect::<Vec<ConceptId>>();
+        label.sort();
+        label
+    }
+
+    pub fn has_concept(&self, x: &Individual, concept: ConceptId) -> bool {
+        self.nodes.get(x).is_some_and(|node| node.label.contains_key(&concept))
+    }
+
+    pub fn have_same_labels(&self, x: &Individual, y: &Individual) -> bool {
+        let (x_label, y_label) = (&self.nodes[x].label, &self.nodes[y].label);
+        x_label.len() == y_label.len() && x_label.keys().all(|c| y_label.contains_key(c))
+    }
+
+    pub fn find_concepts<T, F>(&self, rule_type: RuleType, select: F) -> Vec<(&Individual, ConceptId, T)> where F: Fn(&ConceptNode) -> Option<T> {
+        // The concepts from the todo queue of the rule, which are still in the labels (of the nodes, which are not cached),
+        // along with what `select` extracts from them
+        let queue = match self.todo.get(&rule_type) {
+            Some(queue) => queue,
+            None => return vec![]
+        };
+
+        queue.entries[queue.head..]
+            .iter()
+            .filter(|(x, c)| self.is_pending(x, *c))
+            .filter_map(|(x, c)| select(self.factory.node(*c)).map(|t| (x, *c, t)))
+            .collect()
+    }
+
+    /// Moves the head of the queue over the entries, which are done, i.e. the rule cannot expand them anymore
+    /// (`is_done` should stay true until we backtrack) or their concepts have left the labels.
+    pub fn skip_done<F>(&mut self, rule_type: RuleType, is_done: F) where F: Fn(&CompletionGraph, &Individual, ConceptId) -> bool {
+        let head = match self.todo.get(&rule_type) {
+            Some(queue) => queue.head + queue.entries[queue.head..]
+                .iter()
+                .take_while(|(x, c)| !self.is_pending(x, *c) || is_done(self, x, *c))
+                .count(),
+            None => return
+        };
+        let queue = self.todo.get_mut(&rule_type).unwrap();
+
+        if head > queue.head {
+            self.trail.push(Change::SkipToDo(rule_type, queue.head));
+            queue.head = head;
+        }
+    }
+
+    fn is_pending(&self, x: &Individual, concept: ConceptId) -> bool {
+        self.nodes.get(x).is_some_and(|node| !node.is_cached && node.label.contains_key(&concept))
+    }
+
+    pub fn concept_dependencies(&self, x: &Individual, concept: ConceptId) -> DependencySet {
+        self.nodes.get(x).and_then(|node| node.label.get(&concept)).cloned().unwrap_or_default()
+    }
+
+    /// Adds C to the label of x (unless it is already there) and checks, if it clashes with the label
+    pub fn add_concept(&mut self, x: &Individual, concept: ConceptId, dependencies: &DependencySet) -> bool {
+        if self.has_concept(x, concept) {
+            return false;
+        }
+
+        let negation = self.factory.negation(concept);
+
+        if self.factory.node(concept) == &ConceptNode::Bottom {
+            self.set_clash(dependencies);
+        } else if self.has_concept(x, negation) {
+            let mut clash_dependencies = self.concept_dependencies(x, negation);
+            clash_dependencies.extend(dependencies.iter().cloned());
+            self.set_clash(&clash_dependencies);
+        }
+
+        self.trail.push(Change::AddConcept(x.clone(), concept));
+        self.insert_concept(x, concept, dependencies.clone());
+
+        if let Some(rule_type) = rule_type(&self.factory, concept) {
+            self.todo.entry(rule_type).or_default().entries.push((x.clone(), concept));
+        }
+
+        true
+    }
+
+    pub fn neighbours(&self, x: &Individual) -> Vec<Individual> {
+        // Sorted to keep the expansion deterministic
+        let mut neighbours = self.nodes[x].neighbours.iter().cloned().collect::<Vec<Individual>>();
+        neighbours.sort_by(|y, z| y.name.cmp(&z.name));
+        neighbours
+    }
+
+    pub fn edge(&self, x: &Individual, y: &Individual) -> Vec<&Relation> {
+        // Relations of the edges from x to y (sorted by their names)
+        let mut relations = self.edges.get(&(x.clone(), y.clone())).map_or(vec![], |relations| relations.keys().collect::<Vec<&Relation>>());
+        relations.sort_by(|r, s| (&r.name, r.is_inverse).cmp(&(&s.name, s.is_inverse)));
+        relations
+    }
+
+    pub fn edge_label(&self, x: &Individual, y: &Individual) -> HashSet<Relation> {
+        // All the relations r, for which y is an r-neighbour of x
+        self.edge(x, y).into_iter().cloned()
+            .chain(self.edge(y, x).into_iter().map(|r| r.inverse()))
+            .collect()
+    }
+
+    pub fn edge_dependencies(&self, x: &Individual, y: &Individual) -> DependencySet {
+        // Dependencies of all the edges between x and y (in any direction)
+        [(x.clone(), y.clone()), (y.clone(), x.clone())]
+            .iter()
+            .filter_map(|key| self.edges.get(key))
+            .flat_map(|relations| relations.values().flatten().cloned())
+            .collect()
+    }
+
+    pub fn all_edge_dependencies(&self) -> DependencySet {
+        self.edges.values().flat_map(|relations| relations.values().flatten().cloned()).collect()
+    }
+
+    pub fn add_edge(&mut self, x: &Individual, y: &Individual, relation: &Relation, dependencies: &DependencySet) -> bool {
+        if self.edges.get(&(x.clone(), y.clone())).is_some_and(|relations| relations.contains_key(relation)) {
+            return false;
+        }
+
+        self.trail.push(Change::AddEdge(x.clone(), y.clone(), relation.clone()));
+        self.insert_edge(x, y, relation.clone(), dependencies.clone());
+        true
+    }
+
+    pub fn negative_edges(&self) -> Vec<(&Individual, &Individual, &Relation, &DependencySet)> {
+        self.negative_edges.iter().map(|((x, y, r), dependencies)| (x, y, r, dependencies)).collect()
+    }
+
+    pub fn add_negative_edge(&mut self, x: &Individual, y: &Individual, relation: &Relation, dependencies: &DependencySet) {
+        let key = (x.clone(), y.clone(), relation.clone());
+
+        if !self.negative_edges.contains_key(&key) {
+            self.trail.push(Change::AddNegativeEdge(x.clone(), y.clone(), relation.clone()));
+            self.negative_edges.insert(key, dependencies.clone());
+        }
+    }
+
+    pub fn different_individuals(&self) -> Vec<&HashSet<Individual>> {
+        self.different_individuals.iter().map(|(xs, _)| xs).collect()
+    }
+
+    pub fn add_different_individuals(&mut self, individuals: HashSet<Individual>, dependencies: &DependencySet) {
+        self.trail.push(Change::AddDifferentIndividuals);
+        self.different_individuals.push((individuals, dependencies.clone()));
+    }
+
+    pub fn are_different(&self, x: &Individual, y: &Individual) -> bool {
+        self.different_individuals.iter().any(|(xs, _)| xs.contains(x) && xs.contains(y))
+    }
+
+    pub fn difference_dependencies(&self, x: &Individual, y: &Individual) -> DependencySet {
+        self.different_individuals
+            .iter()
+            .filter(|(xs, _)| xs.contains(x) && xs.contains(y))
+            .flat_map(|(_, dependencies)| dependencies.iter().cloned())
+            .collect()
+    }
+
+    pub fn find_replacement(&self, x: &Individual) -> Individual {
+        // Follows the chain of merges, i.e. finds an individual x has become
+        match self.replacements.get(x) {
+            Some((y, _)) => self.find_replacement(y),
+            None => x.clone()
+        }
+    }
+
+    pub fn replacement_dependencies(&self, x: &Individual) -> DependencySet {
+        // Dependencies of all the merges in the chain, which `find_replacement` follows
+        match self.replacements.get(x) {
+            Some((y, dependencies)) => {
+                let mut dependencies = dependencies.clone();
+                dependencies.extend(self.replacement_dependencies(y));
+                dependencies
+            },
+            None => DependencySet::new()
+        }
+    }
+
+    /// Merges x_old into x_new: all the concepts and edges of x_old are moved to x_new.
+    /// The moved facts depend on the merge itself, i.e. on everything, which has forced it (given in `dependencies`).
+    /// They are moved in a fixed order (concepts by their ids, edges by their relations), so the runs are reproducible.
+    pub fn merge(&mut self, x_old: &Individual, x_new: &Individual, dependencies: &DependencySet) {
+        debug_assert!(self.contains_individual(x_old));
+        debug_assert!(self.contains_individual(x_new));
+
+        let with_merge = |d: &DependencySet| d.iter().chain(dependencies).cloned().collect::<DependencySet>();
+        let rename = |x: &Individual| if x == x_old { x_new.clone() } else { x.clone() };
+
+        for concept in self.label(x_old) {
+            let concept_dependencies = self.concept_dependencies(x_old, concept);
+            self.remove_concept(x_old, concept);
+            self.add_concept(x_new, concept, &with_merge(&concept_dependencies));
+        }
+
+        for y in self.neighbours(x_old) {
+            for (lhs, rhs) in [(x_old.clone(), y.clone()), (y.clone(), x_old.clone())] {
+                let relations = self.edge(&lhs, &rhs).into_iter().cloned().collect::<Vec<Relation>>();
+
+                for relation in relations {
+                    let edge_dependencies = self.edges[&(lhs.clone(), rhs.clone())][&relation].clone();
+                    self.remove_edge(&lhs, &rhs, &relation);
+                    self.add_edge(&rename(&lhs), &rename(&rhs), &relation, &with_merge(&edge_dependencies));
+                }
+            }
+        }
+
+        let mut negative_edges = self.negative_edges.iter()
+            .filter(|((x, y, _), _)| x == x_old || y == x_old)
+            .map(|(key, d)| (key.clone(), d.clone()))
+            .collect::<Vec<_>>();
+        negative_edges.sort_by(|((x, y, r), _), ((z, w, s), _)| {
+            (&x.name, &y.name, &r.name, r.is_inverse).cmp(&(&z.name, &w.name, &s.name, s.is_inverse))
+        });
+
+        for ((x, y, relation), edge_dependencies) in negative_edges {
+            self.trail.push(Change::RemoveNegativeEdge(x.clone(), y.clone(), relation.clone(), edge_dependencies.clone()));
+            self.negative_edges.remove(&(x.clone(), y.clone(), relation.clone()));
+            self.add_negative_edge(&rename(&x), &rename(&y), &relation, &with_merge(&edge_dependencies));
+        }
+
+        for i in 0..self.different_individuals.len() {
+            if !self.different_individuals[i].0.contains(x_old) {
+                continue;
+            }
+
+            if self.different_individuals[i].0.contains(x_new) {
+                let clash_dependencies = with_merge(&self.different_individuals[i].1);
+                self.set_clash(&clash_dependencies);
+            } else {
+                let individuals = self.different_individuals[i].0.clone();
+                self.trail.push(Change::UpdateDifferentIndividuals(i, individuals));
+                self.different_individuals[i].0.remove(x_old);
+                self.different_individuals[i].0.insert(x_new.clone());
+            }
+        }
+
+        if self.unique_name_assumption && !x_old.is_generated() && !x_new.is_generated() {
+            self.set_clash(dependencies);
+        }
+
+        let position = self.individuals.iter().position(|x| x == x_old).unwrap();
+        // The node keeps its flags (e.g. whether it is cached), so we restore it as it was
+        let node = self.nodes.remove(x_old).unwrap();
+        self.trail.push(Change::RemoveIndividual(x_old.clone(), position, node));
+        self.individuals.remove(position);
+
+        self.trail.push(Change::AddReplacement(x_old.clone()));
+        self.replacements.insert(x_old.clone(), (x_new.clone(), dependencies.clone()));
+    }
+
+    pub fn is_cached(&self, x: &Individual) -> bool {
+        self.nodes.get(x).is_some_and(|node| node.is_cached)
+    }
+
+    pub fn set_cached(&mut self, x: &Individual) {
+        self.trail.push(Change::SetCached(x.clone()));
+        self.nodes.get_mut(x).unwrap().is_cached = true;
+    }
+
+    pub fn clash(&self) -> Option<&DependencySet> {
+        self.clash.as_ref()
+    }
+
+    pub fn set_clash(&mut self, dependencies: &DependencySet) {
+        // We keep the first clash: it is enough to know that this branch is closed
+        if self.clash.is_none() {
+            self.trail.push(Change::SetClash);
+            self.clash = Some(dependencies.clone());
+        }
+    }
+
+    pub fn mark(&self) -> usize {
+        self.trail.len()
+    }
+
+    /// Reverts all the changes made after the mark
+    pub fn undo(&mut self, mark: usize) {
+        while self.trail.len() > mark {
+            match self.trail.pop().unwrap() {
+                Change::AddIndividual(x) => {
+                    self.individuals.pop();
+                    self.nodes.remove(&x);
+                },
+                Change::RemoveIndividual(x, position, node) => {
+                    self.individuals.insert(position, x.clone());
+                    self.nodes.insert(x, node);
+                },
+                Change::AddConcept(x, concept) => {
+                    self.nodes.get_mut(&x).unwrap().label.remove(&concept);
+
+                    if let Some(rule_type) = rule_type(&self.factory, concept) {
+                        self.todo.get_mut(&rule_type).unwrap().entries.pop();
+                    }
+                },
+                Change::RemoveConcept(x, concept, dependencies) => self.insert_concept(&x, concept, dependencies),
+                Change::AddEdge(x, y, relation) => self.delete_edge(&x, &y, &relation),
+                Change::RemoveEdge(x, y, relation, dependencies) => self.insert_edge(&x, &y, relation, dependencies),
+                Change::AddNegativeEdge(x, y, relation) => {
+                    self.negative_edges.remove(&(x, y, relation));
+                },
+                Change::RemoveNegativeEdge(x, y, relation, dependencies) => {
+                    self.negative_edges.insert((x, y, relation), dependencies);
+                },
+                Change::AddDifferentIndividuals => {
+                    self.different_individuals.pop();
+                },
+                Change::UpdateDifferentIndividuals(i, individuals) => self.different_individuals[i].0 = individuals,
+                Change::AddReplacement(x) => {
+                    self.replacements.remove(&x);
+                },
+                Change::SetCached(x) => self.nodes.get_mut(&x).unwrap().is_cached = false,
+                Change::SkipToDo(rule_type, head) => self.todo.get_mut(&rule_type).unwrap().head = head,
+                Change::SetClash => self.clash = None
+            }
+        }
+    }
+
+    fn remove_concept(&mut self, x: &Individual, concept: ConceptId) {
+        if let Some(dependencies) = self.nodes.get_mut(x).unwrap().label.remove(&concept) {
+            self.trail.push(Change::RemoveConcept(x.clone(), concept, dependencies));
+        }
+    }
+
+    fn remove_edge(&mut self, x: &Individual, y: &Individual, relation: &Relation) {
+        let dependencies = self.edges.get(&(x.clone(), y.clone())).and_then(|relations| relations.get(relation)).cloned();
+
+        if let Some(dependencies) = dependencies {
+            self.trail.push(Change::RemoveEdge(x.clone(), y.clone(), relation.clone(), dependencies));
+            self.delete_edge(x, y, relation);
+        }
+    }
+
+    // The functions below change the graph without recording it on the trail
+
+    fn insert_concept(&mut self, x: &Individual, concept: ConceptId, dependencies: DependencySet) {
+        self.nodes.get_mut(x).unwrap().label.insert(concept, dependencies);
+    }
+
+    fn insert_edge(&mut self, x: &Individual, y: &Individual, relation: Relation, dependencies: DependencySet) {
+        self.edges.entry((x.clone(), y.clone())).or_default().insert(relation, dependencies);
+        self.nodes.get_mut(x).unwrap().neighbours.insert(y.clone());
+        self.nodes.get_mut(y).unwrap().neighbours.insert(x.clone());
+    }
+
+    fn delete_edge(&mut self, x: &Individual, y: &Individual, relation: &Relation) {
+        let key = (x.clone(), y.clone());
+
+        if let Some(relations) = self.edges.get_mut(&key) {
+            relations.remove(relation);
+
+            if relations.is_empty() {
+                self.edges.remove(&key);
+            }
+        }
+
+        if !self.edges.contains_key(&key) && !self.edges.contains_key(&(y.clone(), x.clone())) {
+            // The nodes can be already removed, if we are undoing their creation
+            if let Some(node) = self.nodes.get_mut(x) { node.neighbours.remove(y); }
+            if let Some(node) = self.nodes.get_mut(y) { node.neighbours.remove(x); }
+        }
+    }
+}
+
+fn rule_type(factory: &ConceptFactory, concept: ConceptId) -> Option<RuleType> {
+    // The concept names (and their negations) are unfolded lazily
+    match factory.node(concept) {
+        ConceptNode::And(_) => Some(RuleType::Conjunction),
+        ConceptNode::Or(_) => Some(RuleType::Disjunction),
+        ConceptNode::Atomic(_) => Some(RuleType::Unfolding),
+        ConceptNode::Not(c) => match factory.node(*c) {
+            ConceptNode::Nominal(_) => Some(RuleType::NegatedNominal),
+            _ => Some(RuleType::Unfolding)
+        },
+        ConceptNode::Nominal(_) => Some(RuleType::Nominal),
+        ConceptNode::Only(..) => Some(RuleType::Only),
+        ConceptNode::Some(..) => Some(RuleType::Some),
+        ConceptNode::AtLeast(..) => Some(RuleType::AtLeast),
+        ConceptNode::AtMost(..) => Some(RuleType::AtMost),
+        ConceptNode::Top | ConceptNode::Bottom => None
+    }
+}
+
+impl fmt::Display for CompletionGraph {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", self.to_abox())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use abox::parse_abox;
+    use concept::parse_concept;
+
+    #[test]
+    fn test_undo() {
+        let mut graph = CompletionGraph::from_abox(&parse_abox("r[a, b]\nA[a]\n(not B)[b]").unwrap());
+        let (a, b) = (Individual { name: "a".to_string() }, Individual { name: "b".to_string() });
+        let label_size = graph.label(&a).len();
+        let mark = graph.mark();
+
+        let x = graph.create_new_individual();
+        graph.set_cached(&x);
+        let mark_cached = graph.mark();
+        graph.add_edge(&b, &x, &Relation::new("s"), &DependencySet::from_iter(vec![0]));
+        let b_concept = graph.factory.intern(&*parse_concept("B").unwrap());
+        graph.add_concept(&x, b_concept, &DependencySet::from_iter(vec![0]));
+        graph.merge(&x, &b, &DependencySet::from_iter(vec![1]));
+
+        assert_eq!(graph.clash(), Some(&DependencySet::from_iter(vec![0, 1])));
+        assert_eq!(graph.edge(&b, &b), vec![&Relation::new("s")]);
+        assert_eq!(graph.neighbours(&a), vec![b.clone()]);
+
+        // The merged node is restored along with its cached flag
+        graph.undo(mark_cached);
+        assert!(graph.is_cached(&x));
+        assert!(graph.edge(&b, &x).is_empty());
+
+        graph.undo(mark);
+
+        assert!(graph.clash().is_none());
+        assert!(!graph.contains_individual(&x));
+        assert!(graph.edge(&b, &b).is_empty());
+        assert_eq!(graph.to_abox().individuals.len(), 2);
+        assert_eq!(graph.label(&a).len(), label_size);
+        assert!(graph.has_concept(&a, graph.factory.find(&ConceptNode::Atomic("A".to_string())).unwrap()));
+    }
+
+    #[test]
+    fn test_todo_queues() {
+        let mut graph = CompletionGraph::from_abox(&parse_abox("(or (A B))[a]\n(and (C D))[a]\n(or (C E))[b]").unwrap());
+        let b = Individual { name: "b".to_string() };
+        let disjunctions = |graph: &CompletionGraph| {
+            graph.find_concepts(RuleType::Disjunction, |_| Some(())).into_iter().map(|(x, _, _)| x.name.clone()).collect::<Vec<String>>()
+        };
+        let is_satisfied = |graph: &CompletionGraph, x: &Individual, c: ConceptId| match graph.factory.node(c) {
+            ConceptNode::Or(cs) => cs.iter().any(|&sc| graph.has_concept(x, sc)),
+            _ => true
+        };
+
+        assert_eq!(disjunctions(&graph), vec!["a", "b"]);
+        assert_eq!(graph.find_concepts(RuleType::Conjunction, |_| Some(())).len(), 1);
+
+        let mark = graph.mark();
+        let a_concept = graph.factory.intern(&*parse_concept("A").unwrap());
+        graph.add_concept(&Individual { name: "a".to_string() }, a_concept, &DependencySet::new());
+        graph.skip_done(RuleType::Disjunction, is_satisfied);
+        assert_eq!(disjunctions(&graph), vec!["b"]);
+
+        // A new concept goes to the end of its queue, and undoing brings back the entries, which were done
+        let e_concept = graph.factory.intern(&*parse_concept("or (E F)").unwrap());
+        graph.add_concept(&b, e_concept, &DependencySet::new());
+        assert_eq!(disjunctions(&graph), vec!["b", "b"]);
+
+        graph.undo(mark);
+        assert_eq!(disjunctions(&graph), vec!["a", "b"]);
+    }
+}
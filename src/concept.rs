@@ -1,156 +1,299 @@
 use std::fmt;
 use std::hash;
 use std::clone::Clone;
-use std::any::{Any, TypeId};
-use std::marker::Sized;
+use std::collections::HashSet;
+use std::iter::FromIterator;
 
+use error::ParseError;
 
-fn extract_concepts(concepts_str: &str) -> Vec<Box<dyn Concept>> {
+
+const KEYWORDS: [&str; 9] = ["and", "or", "not", "only", "some", ">=", "<=", "=", "oneOf"];
+
+
+fn extract_concepts(concepts_str: &str, source: &str) -> Result<Vec<Box<dyn Concept>>, ParseError> {
     // Takes a concepts string, seperated by whitespace and wrapped up in brackets,
     // parses them individually and returns a vector of concepts.
     let mut concepts_str = concepts_str.trim();
     debug!("Extracting concepts: {}", concepts_str);
     let mut concepts: Vec<Box<dyn Concept>> = Vec::new();
+
+    while !concepts_str.is_empty() {
+        // We should extract something, but we have two alternatives:
+        // - this is a compound concept of the form "(My .. Compound .. Concept)"
+        // - this is an atomic concept of the form "MyConcept"
+        let concept_len = if concepts_str.starts_with('(') {
+            find_closing_bracket(concepts_str)
+                .ok_or_else(|| ParseError::new(source, &concepts_str[..1], "a matching `)` for this bracket"))? + 1
+        } else if concepts_str.starts_with('{') {
+            concepts_str.find('}')
+                .ok_or_else(|| ParseError::new(source, &concepts_str[..1], "a matching `}` for this bracket"))? + 1
+        } else {
+            let token = &concepts_str[..token_len(concepts_str)];
+
+            if token.is_empty() {
+                return Err(ParseError::new(source, &concepts_str[..1], "a concept"));
+            } else if KEYWORDS.contains(&token) {
+                return Err(ParseError::new(source, token, "an atomic concept (wrap non-atomic ones into brackets)"));
+            }
+
+            token.len()
+        };
+
+        let concept_str = &concepts_str[..concept_len];
+        debug!("Found a concept: {}", concept_str);
+        concepts.push(parse_concept_in(concept_str, source)?);
+        concepts_str = concepts_str[concept_len..].trim();
+    }
+
+    Ok(concepts)
+}
+
+
+fn find_closing_bracket(s: &str) -> Option<usize> {
+    // Returns an index of the bracket, which closes the opening one at the beginning of `s`
+    debug_assert!(s.starts_with('('));
     let mut curr_depth = 0;
-    let mut i = 0;
 
-    while concepts_str.len() > 0 {
-        if &concepts_str[i..(i+1)] == "(" {
+    for (i, c) in s.char_indices() {
+        if c == '(' {
             curr_depth += 1; // Going a level deeper
-        } else if &concepts_str[i..(i+1)] == ")" {
+        } else if c == ')' {
             curr_depth -= 1; // Going a level out
-        }
 
-        if curr_depth == 0 {
-            // Ok, we should extract something, but we have two alternatives:
-            // - this is a compound concept of the form "(My .. Compound .. Concept)"
-            // - this is an atomic concept of the form "MyConcept"
-            let concept_str;
-
-            if &concepts_str[..1] == "(" {
-                assert!(&concepts_str[i..(i+ 1)] == ")", "Compound concept is in a bad format: {}", concepts_str);
-                concept_str = &concepts_str[..(i+1)];
-                debug!("Found a compound concept: {}", concept_str);
-                concepts.push(parse_concept(concept_str));
-            } else {
-                let space_idx = concepts_str.chars().position(|c| c == ' ').unwrap_or(concepts_str.len());
-                concept_str = &concepts_str[..space_idx];
-                debug!("Found an atomic concept: {}", concept_str);
-                concepts.push(parse_concept(concept_str));
+            if curr_depth == 0 {
+                return Some(i);
             }
-
-            i = 0;
-            concepts_str = concepts_str[concept_str.len()..].trim();
-        } else {
-            i += 1;
         }
     }
 
-    debug_assert!(concepts.len() > 0);
+    None
+}
+
+
+fn token_len(s: &str) -> usize {
+    // Length of the leading token, i.e. everything until a whitespace or a bracket
+    s.find(|c: char| c.is_whitespace() || c == '(' || c == ')').unwrap_or(s.len())
+}
+
+
+fn strip_keyword<'a>(concept_str: &'a str, keyword: &str) -> Option<&'a str> {
+    // Word keywords must be followed by a whitespace or a bracket, so that
+    // atomic concepts like "Order" or "Android" are not mistaken for "or"/"and"
+    if !concept_str.starts_with(keyword) {
+        return None;
+    }
+
+    let rest = &concept_str[keyword.len()..];
+    let is_word = keyword.chars().all(char::is_alphabetic);
+
+    match rest.chars().next() {
+        Some(c) if is_word && !c.is_whitespace() && c != '(' => None,
+        _ => Some(rest)
+    }
+}
+
+
+fn parse_subconcepts<'a>(concept_str: &'a str, rest: &'a str, source: &'a str) -> Result<Vec<Box<dyn Concept>>, ParseError> {
+    // Parses the "(A B C)" part of "and (A B C)" and "or (A B C)" concepts
+    let rest = rest.trim();
+
+    if !rest.starts_with('(') {
+        return Err(ParseError::new(source, &rest[..token_len(rest)], "a bracketed list of subconcepts, e.g. `(A B)`"));
+    }
+
+    let end_idx = find_closing_bracket(rest)
+        .ok_or_else(|| ParseError::new(source, &rest[..1], "a matching `)` for this bracket"))?;
+
+    if end_idx + 1 != rest.len() {
+        return Err(ParseError::new(source, rest[end_idx + 1..].trim(), "the end of the concept"));
+    }
+
+    let subconcepts = extract_concepts(&rest[1..end_idx], source)?;
+
+    if subconcepts.len() < 2 {
+        return Err(ParseError::new(source, concept_str, "at least two subconcepts"));
+    }
+
+    Ok(subconcepts)
+}
+
+
+fn parse_relation_and_concept<'a>(rest: &'a str, source: &'a str) -> Result<(Relation, Box<dyn Concept>), ParseError> {
+    // Parses the "r C" part of "some r C", "only r C", ">= n r C" and "<= n r C" concepts
+    let rest = rest.trim();
+    let relation_name = &rest[..token_len(rest)];
+
+    if relation_name.is_empty() || relation_name == "-" || KEYWORDS.contains(&relation_name) {
+        let token = if relation_name.is_empty() { &rest[..rest.len().min(1)] } else { relation_name };
+        return Err(ParseError::new(source, token, "a relation name"));
+    }
+
+    let subconcept = parse_concept_in(&rest[relation_name.len()..], source)?;
+
+    Ok((Relation::new(relation_name), subconcept))
+}
+
+
+fn parse_amount<'a>(rest: &'a str, source: &'a str) -> Result<(usize, &'a str), ParseError> {
+    // Parses the "n" part of ">= n r C" and "<= n r C" concepts
+    let rest = rest.trim();
+    let amount_str = &rest[..token_len(rest)];
+    let amount = amount_str.parse::<usize>()
+        .map_err(|_| ParseError::new(source, amount_str, "a non-negative number"))?;
+
+    Ok((amount, &rest[amount_str.len()..]))
+}
+
+
+fn parse_individuals<'a>(individuals_str: &'a str, source: &'a str) -> Result<Vec<Individual>, ParseError> {
+    // Parses the "a b c" part of "{a b c}" and "oneOf (a b c)" concepts
+    let names = individuals_str
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|n| !n.is_empty())
+        .collect::<Vec<&str>>();
+
+    if names.is_empty() {
+        return Err(ParseError::new(source, individuals_str, "at least one individual name"));
+    } else if let Some(bad_name) = names.iter().find(|n| n.contains(|c: char| "(){}[]".contains(c))) {
+        return Err(ParseError::new(source, bad_name, "an individual name"));
+    }
+
+    Ok(names.into_iter().map(|n| Individual { name: n.to_string() }).collect())
+}
+
+
+pub fn extract_atomic_names(concept: &dyn Concept) -> HashSet<String> {
+    match concept.downcast_ref::<AtomicConcept>() {
+        Some(atomic) => HashSet::from_iter(vec![atomic.name.clone()]),
+        None => concept.children().into_iter().flat_map(extract_atomic_names).collect()
+    }
+}
+
+
+pub fn extract_nominal_individuals(concept: &dyn Concept) -> HashSet<Individual> {
+    match concept.downcast_ref::<NominalConcept>() {
+        Some(nominal) => nominal.individuals.iter().cloned().collect(),
+        None => concept.children().into_iter().flat_map(extract_nominal_individuals).collect()
+    }
+}
+
 
-    concepts
+pub fn extract_number_restrictions(concept: &dyn Concept) -> Vec<(&Relation, &dyn Concept)> {
+    // At-least and at-most concepts along with their relations
+    let relation = concept.downcast_ref::<AtLeastConcept>().map(|c| &c.relation)
+        .or_else(|| concept.downcast_ref::<AtMostConcept>().map(|c| &c.relation));
+
+    relation.map(|r| (r, concept)).into_iter()
+        .chain(concept.children().into_iter().flat_map(extract_number_restrictions))
+        .collect()
+}
+
+
+pub fn parse_concept(concept_str: &str) -> Result<Box<dyn Concept>, ParseError> {
+    parse_concept_in(concept_str, concept_str)
 }
 
 
-pub fn parse_concept(concept_str: &str) -> Box<dyn Concept> {
-    // Parses concept or panics if the string is not a correct concept
-    // let mut words = concept_str.split(' ').collect();
+pub fn parse_concept_in(concept_str: &str, source: &str) -> Result<Box<dyn Concept>, ParseError> {
+    // Parses a concept, which is a part of a bigger `source` string (e.g. an ABox/TBox line).
+    // `source` is only needed to report the location of an error.
     let concept_str = concept_str.trim();
 
     debug!("Parsing concept: {}", concept_str);
 
-    if &concept_str[..1] == "(" {
+    if concept_str.is_empty() {
+        Err(ParseError::new(source, concept_str, "a concept"))
+    } else if concept_str.starts_with('(') {
         // Our concept is wrapped up into brackets "(..)"
-        // debug!("Parsing concept result: {}", parse_concept(&concept_str[1..(concept_str.len() - 1)]));
-        parse_concept(&concept_str[1..(concept_str.len() - 1)])
-    } else if concept_str.len() > 3 && &concept_str[..3] == "and" {
-        // Concept has the format "and (A B C)"
-        let subconcepts = extract_concepts(&concept_str[5..concept_str.len()-1]);
-        assert!(subconcepts.len() >= 2, "Too few subconcepts inside a conjunction concept: {}", concept_str);
-        // debug!("Parsing concept result: {}", Box::new(ConjunctionConcept { subconcepts: subconcepts.clone() }));
-        Box::new(ConjunctionConcept { subconcepts: subconcepts })
-    } else if concept_str.len() > 2 && &concept_str[..2] == "or" {
-        // Concept has the format "or (A B C)"
-        let subconcepts = extract_concepts(&concept_str[4..concept_str.len()-1]);
-        assert!(subconcepts.len() >= 2, "Too few subconcepts inside a conjunction concept: {}", concept_str);
-        // debug!("Parsing concept result: {}", Box::new(DisjunctionConcept { subconcepts: subconcepts.clone() }));
-        Box::new(DisjunctionConcept { subconcepts: subconcepts })
-    } else if concept_str.len() > 4 && &concept_str[..4] == "only" {
-        let space_idx = concept_str[5..].chars().position(|c| c == ' ')
-            .expect(&format!("Bad format for `only` concept: {}", concept_str));
-        let relation_name = concept_str[5..5 + space_idx].to_string();
+        let end_idx = find_closing_bracket(concept_str)
+            .ok_or_else(|| ParseError::new(source, &concept_str[..1], "a matching `)` for this bracket"))?;
 
-        Box::new(OnlyConcept {
-            subconcept: parse_concept(&concept_str[(5 + &relation_name.len() + 1)..]),
-            relation: Relation {name: relation_name},
-        })
-    } else if concept_str.len() > 4 && &concept_str[..4] == "some" {
-        let space_idx = concept_str[5..].chars().position(|c| c == ' ')
-            .expect(&format!("Bad format for `only` concept: {}", concept_str));
-        let relation_name = concept_str[5..5 + space_idx].to_string();
-
-        Box::new(SomeConcept {
-            subconcept: parse_concept(&concept_str[(5 + &relation_name.len() + 1)..]),
-            relation: Relation {name: relation_name},
-        })
-    } else if concept_str.len() > 3 && &concept_str[..3] == "not" {
-        Box::new(NotConcept { subconcept: parse_concept(&concept_str[3..]) })
-    } else if concept_str.len() > 2 && &concept_str[..2] == ">=" {
-        let concept_str = concept_str[2..].trim(); // Has the from "2 r C" now
-
-        // Extract amount
-        let space_idx = concept_str.chars().position(|c| c == ' ')
-            .expect(&format!("Bad format to extract amount from `at_least` concept: {}", concept_str));
-        let amount = concept_str[..space_idx].parse::<usize>()
-            .expect(&format!("Bad format of `amount` for `at_least` concept: {}", concept_str));
-
-        // Extract relation name
-        let concept_str = concept_str[space_idx..].trim(); // Has the form "relation Concept" now
-        let space_idx = concept_str.chars().position(|c| c == ' ')
-            .expect(&format!("Bad format to extract relation from `at_least` concept: {}", concept_str));
-        let relation_name = concept_str[..space_idx].to_string();
-
-        debug!("AtMost amount: {}", amount);
-        debug!("AtMost relation_name: {}", relation_name);
+        let concept = parse_concept_in(&concept_str[1..end_idx], source)?;
 
-        Box::new(AtLeastConcept {
-            amount: amount,
-            subconcept: parse_concept(&concept_str[(&relation_name.len() + 1)..]),
-            relation: Relation {name: relation_name}
-        })
-    } else if concept_str.len() > 3 && &concept_str[..2] == "<=" {
-        // TODO: this is too similar to at-least concept parsing...
-        let concept_str = concept_str[2..].trim(); // Has the from "2 r C" now
+        if end_idx + 1 != concept_str.len() {
+            return Err(ParseError::new(source, concept_str[end_idx + 1..].trim(), "the end of the concept"));
+        }
 
-        // Extract amount
-        let space_idx = concept_str.chars().position(|c| c == ' ')
-            .expect(&format!("Bad format to extract amount from `at_most` concept: {}", concept_str));
-        let amount = concept_str[..space_idx].parse::<usize>()
-            .expect(&format!("Bad format of `amount` for `at_most` concept: {}", concept_str));
+        Ok(concept)
+    } else if concept_str.starts_with('{') {
+        // Nominal concept has the format "{a}" or "{a b c}"
+        if !concept_str.ends_with('}') {
+            return Err(ParseError::new(source, &concept_str[concept_str.len()..], "a closing `}`"));
+        }
 
-        // Extract relation name
-        let concept_str = concept_str[space_idx..].trim(); // Has the form "myRelation MyConcept" now
-        let space_idx = concept_str.chars().position(|c| c == ' ')
-            .expect(&format!("Bad format to extract relation from `at_most` concept: {}", concept_str));
-        let relation_name = concept_str[..space_idx].to_string();
+        let individuals = parse_individuals(&concept_str[1..concept_str.len() - 1], source)?;
+        Ok(Box::new(NominalConcept { individuals }))
+    } else if let Some(rest) = strip_keyword(concept_str, "oneOf") {
+        // Nominal concept has the format "oneOf (a b c)"
+        let rest = rest.trim();
 
-        debug!("AtMost amount: {}", amount);
-        debug!("AtMost relation_name: {}", relation_name);
+        if !rest.starts_with('(') || !rest.ends_with(')') {
+            return Err(ParseError::new(source, rest, "a bracketed list of individuals, e.g. `(a b)`"));
+        }
 
-        Box::new(AtMostConcept {
-            amount: amount,
-            subconcept: parse_concept(&concept_str[(&relation_name.len() + 1)..]),
-            relation: Relation {name: relation_name}
-        })
+        let individuals = parse_individuals(&rest[1..rest.len() - 1], source)?;
+        Ok(Box::new(NominalConcept { individuals }))
+    } else if let Some(rest) = strip_keyword(concept_str, "and") {
+        // Concept has the format "and (A B C)"
+        let subconcepts = parse_subconcepts(concept_str, rest, source)?;
+        Ok(Box::new(ConjunctionConcept { subconcepts }))
+    } else if let Some(rest) = strip_keyword(concept_str, "or") {
+        // Concept has the format "or (A B C)"
+        let subconcepts = parse_subconcepts(concept_str, rest, source)?;
+        Ok(Box::new(DisjunctionConcept { subconcepts }))
+    } else if let Some(rest) = strip_keyword(concept_str, "only") {
+        let (relation, subconcept) = parse_relation_and_concept(rest, source)?;
+        Ok(Box::new(OnlyConcept { subconcept, relation }))
+    } else if let Some(rest) = strip_keyword(concept_str, "some") {
+        let (relation, subconcept) = parse_relation_and_concept(rest, source)?;
+        Ok(Box::new(SomeConcept { subconcept, relation }))
+    } else if let Some(rest) = strip_keyword(concept_str, "not") {
+        Ok(Box::new(NotConcept { subconcept: parse_concept_in(rest, source)? }))
+    } else if let Some(rest) = strip_keyword(concept_str, ">=") {
+        let (amount, rest) = parse_amount(rest, source)?;
+        let (relation, subconcept) = parse_relation_and_concept(rest, source)?;
+        Ok(Box::new(AtLeastConcept { amount, relation, subconcept }))
+    } else if let Some(rest) = strip_keyword(concept_str, "<=") {
+        let (amount, rest) = parse_amount(rest, source)?;
+        let (relation, subconcept) = parse_relation_and_concept(rest, source)?;
+        Ok(Box::new(AtMostConcept { amount, relation, subconcept }))
+    } else if let Some(rest) = strip_keyword(concept_str, "=") {
+        // "= n r C" is a shortcut for "and ((>= n r C) (<= n r C))"
+        let (amount, rest) = parse_amount(rest, source)?;
+        let (relation, subconcept) = parse_relation_and_concept(rest, source)?;
+        let at_most = Box::new(AtMostConcept { amount, relation: relation.clone(), subconcept: subconcept.clone() });
+
+        match amount {
+            0 => Ok(at_most),
+            _ => Ok(Box::new(ConjunctionConcept {
+                subconcepts: vec![Box::new(AtLeastConcept { amount, relation, subconcept }), at_most]
+            }))
+        }
     } else {
         // This is an Atomic Concept!
-        Box::new(AtomicConcept { name: concept_str.to_string() })
+        let name_len = token_len(concept_str);
+
+        if name_len == 0 {
+            return Err(ParseError::new(source, &concept_str[..1], "a concept"));
+        } else if name_len != concept_str.len() {
+            let rest = concept_str[name_len..].trim();
+            return Err(ParseError::new(source, &rest[..token_len(rest).max(1)],
+                "the end of the concept (non-atomic concepts look like `and (A B)`, `some r C`, etc.)"));
+        }
+
+        match concept_str {
+            "__TOP__" | "⊤" => Ok(Box::new(TopConcept)),
+            "__BOTTOM__" | "⊥" => Ok(Box::new(BottomConcept)),
+            _ => Ok(Box::new(AtomicConcept { name: concept_str.to_string() }))
+        }
     }
 }
 
 
 #[derive(PartialEq)]
 pub enum ConceptType {
+    Top,
+    Bottom,
     Atomic,
     Not,
     Conjunction,
@@ -158,7 +301,8 @@ pub enum ConceptType {
     Only,
     Some,
     AtLeast,
-    AtMost
+    AtMost,
+    Nominal
 }
 
 pub trait Concept: fmt::Debug + fmt::Display + mopa::Any + ConceptClone {
@@ -167,10 +311,13 @@ pub trait Concept: fmt::Debug + fmt::Display + mopa::Any + ConceptClone {
 
     fn negate(&self) -> Box<dyn Concept> {
         // Box::new(NotConcept{ subconcept: Box::new(self.clone()) })
-        Box::new(NotConcept{ subconcept: Box::new(self).clone_box() })
+        Box::new(NotConcept{ subconcept: self.clone_box() })
     }
 
-    fn replace_concept(&self, concept_old: Box<dyn Concept>, concept_new: Box<dyn Concept>) -> Box<dyn Concept>;
+    fn children(&self) -> Vec<&dyn Concept> {
+        // Direct subconcepts, i.e. nothing for atomic concepts, nominals, top and bottom
+        vec![]
+    }
 }
 
 mopafy!(Concept);
@@ -203,11 +350,28 @@ impl Clone for Box<dyn Concept> {
 }
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
-pub struct Relation { pub name: String }
+pub struct Relation {
+    pub name: String,
+    pub is_inverse: bool
+}
+
+impl Relation {
+    pub fn new(relation_str: &str) -> Relation {
+        // Inverse relations are written with a trailing minus: "hasChild-"
+        match relation_str.strip_suffix('-') {
+            Some(name) => Relation { name: name.to_string(), is_inverse: true },
+            None => Relation { name: relation_str.to_string(), is_inverse: false }
+        }
+    }
+
+    pub fn inverse(&self) -> Relation {
+        Relation { name: self.name.clone(), is_inverse: !self.is_inverse }
+    }
+}
 
 impl fmt::Display for Relation {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        write!(fmt, "{}(x, y)", self.name)
+        write!(fmt, "{}{}", self.name, if self.is_inverse { "-" } else { "" })
     }
 }
 
@@ -216,18 +380,23 @@ pub struct Individual { pub name: String }
 
 
 impl Individual {
+    pub fn is_generated(&self) -> bool {
+        // Individuals created by the tableau are named "x_#<number>"
+        self.name.starts_with("x_#")
+    }
+
     pub fn is_younger(&self, other: &Individual) -> bool {
         // Checks if lhs is possibly younger than `other`
-        if self.name.starts_with("x_#") && other.name.starts_with("x_#") {
+        if self.is_generated() && other.is_generated() {
             let self_num = self.name[3..].parse::<usize>().unwrap();
             let rhs_num = other.name[3..].parse::<usize>().unwrap();
 
             // smaller number => rhs has appeared earlier => it is older
             self_num > rhs_num
-        } else if self.name.starts_with("x_#") {
-            return true; // self is an auto-generated variable
+        } else if self.is_generated() {
+            true // self is an auto-generated variable
         } else {
-            return false; // self is an original (ancient) individual
+            false // self is an original (ancient) individual
         }
     }
 }
@@ -238,6 +407,48 @@ impl fmt::Display for Individual {
     }
 }
 
+#[derive(Debug, Clone, Hash)]
+pub struct TopConcept;
+
+impl Concept for TopConcept {
+    fn convert_to_nnf(&self) -> Box<dyn Concept> {
+        Box::new(TopConcept)
+    }
+
+    fn concept_type(&self) -> ConceptType { ConceptType::Top }
+
+    fn negate(&self) -> Box<dyn Concept> {
+        Box::new(BottomConcept)
+    }
+}
+
+impl fmt::Display for TopConcept {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "__TOP__")
+    }
+}
+
+#[derive(Debug, Clone, Hash)]
+pub struct BottomConcept;
+
+impl Concept for BottomConcept {
+    fn convert_to_nnf(&self) -> Box<dyn Concept> {
+        Box::new(BottomConcept)
+    }
+
+    fn concept_type(&self) -> ConceptType { ConceptType::Bottom }
+
+    fn negate(&self) -> Box<dyn Concept> {
+        Box::new(TopConcept)
+    }
+}
+
+impl fmt::Display for BottomConcept {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "__BOTTOM__")
+    }
+}
+
 #[derive(Debug, Clone, Hash)]
 pub struct AtomicConcept { pub name: String }
 
@@ -246,13 +457,6 @@ impl Concept for AtomicConcept {
         Box::new(self.clone())
     }
     fn concept_type(&self) -> ConceptType { ConceptType::Atomic }
-
-    fn replace_concept(&self, concept_old: Box<dyn Concept>, concept_new: Box<dyn Concept>) -> Box<dyn Concept> {
-        match self.to_string() == concept_old.to_string() {
-            true => concept_new,
-            false => Box::new(self.clone()) as Box<dyn Concept>
-        }
-    }
 }
 
 impl fmt::Display for AtomicConcept {
@@ -269,10 +473,16 @@ pub struct NotConcept {
 impl Concept for NotConcept {
     fn convert_to_nnf(&self) -> Box<dyn Concept> {
         match self.subconcept.concept_type() {
+            ConceptType::Top => Box::new(BottomConcept),
+            ConceptType::Bottom => Box::new(TopConcept),
             ConceptType::Atomic => {
                 let subconcept = self.subconcept.downcast_ref::<AtomicConcept>().unwrap();
                 Box::new(NotConcept { subconcept: Box::new(subconcept.clone()) })
             },
+            ConceptType::Nominal => {
+                // Negated nominals are in NNF already
+                Box::new(self.clone())
+            },
             ConceptType::Not => {
                 let subconcept = self.subconcept.downcast_ref::<NotConcept>().unwrap();
                 subconcept.subconcept.convert_to_nnf()
@@ -280,23 +490,16 @@ impl Concept for NotConcept {
             ConceptType::Conjunction => {
                 // not and (A B C) => or ((not A) (not B) (not C))
                 let subconcept = self.subconcept.downcast_ref::<ConjunctionConcept>().unwrap();
-                // Box::new(AtomicConcept { name: "123".to_string() })
-                Box::new(DisjunctionConcept {
-                    subconcepts: subconcept.clone().subconcepts.iter()
-                        .map(|c| { c.negate() })
-                        .map(|c| {c.convert_to_nnf()})
-                        .collect()
-                })
+                DisjunctionConcept {
+                    subconcepts: subconcept.subconcepts.iter().map(|c| c.negate()).collect()
+                }.convert_to_nnf()
             },
             ConceptType::Disjunction => {
                 // not [or (A B C)] => and ((not A) (not B) (not C))
                 let subconcept = self.subconcept.downcast_ref::<DisjunctionConcept>().unwrap();
-                Box::new(ConjunctionConcept {
-                    subconcepts: subconcept.clone().subconcepts.iter()
-                        .map(|c| { Box::new(NotConcept{ subconcept: c.clone() }) })
-                        .map(|c| {c.convert_to_nnf()})
-                        .collect()
-                })
+                ConjunctionConcept {
+                    subconcepts: subconcept.subconcepts.iter().map(|c| c.negate()).collect()
+                }.convert_to_nnf()
             },
             ConceptType::Only => {
                 // not [only A] => some [not A]
@@ -317,6 +520,11 @@ impl Concept for NotConcept {
             ConceptType::AtLeast => {
                 // not [some A] => only [not A]
                 let subconcept = self.subconcept.downcast_ref::<AtLeastConcept>().unwrap();
+
+                if subconcept.amount == 0 {
+                    return Box::new(BottomConcept); // ">= 0 r C" always holds
+                }
+
                 Box::new(AtMostConcept {
                     amount: subconcept.amount - 1,
                     relation: subconcept.relation.clone(),
@@ -341,13 +549,8 @@ impl Concept for NotConcept {
         self.subconcept.clone() as Box<dyn Concept>
     }
 
-    fn replace_concept(&self, concept_old: Box<dyn Concept>, concept_new: Box<dyn Concept>) -> Box<dyn Concept> {
-        match self.to_string() == concept_old.to_string() {
-            true => concept_new,
-            false => Box::new(NotConcept {
-                subconcept: self.subconcept.replace_concept(concept_old, concept_new)
-            }) as Box<dyn Concept>
-        }
+    fn children(&self) -> Vec<&dyn Concept> {
+        vec![&*self.subconcept]
     }
 }
 
@@ -364,30 +567,32 @@ pub struct ConjunctionConcept {
 
 impl Concept for ConjunctionConcept {
     fn convert_to_nnf(&self) -> Box<dyn Concept> {
-        Box::new(ConjunctionConcept {
-            subconcepts: self.subconcepts.iter().map(|c| { c.convert_to_nnf() }).collect()
-        })
+        // ⊤ can be dropped from a conjunction, while ⊥ turns the whole conjunction into ⊥
+        let mut subconcepts = self.subconcepts.iter()
+            .map(|c| c.convert_to_nnf())
+            .filter(|c| c.concept_type() != ConceptType::Top)
+            .collect::<Vec<Box<dyn Concept>>>();
+
+        if subconcepts.iter().any(|c| c.concept_type() == ConceptType::Bottom) {
+            Box::new(BottomConcept)
+        } else if subconcepts.len() > 1 {
+            Box::new(ConjunctionConcept { subconcepts })
+        } else {
+            subconcepts.pop().unwrap_or_else(|| Box::new(TopConcept))
+        }
     }
 
     fn concept_type(&self) -> ConceptType { ConceptType::Conjunction }
 
-    fn replace_concept(&self, concept_old: Box<dyn Concept>, concept_new: Box<dyn Concept>) -> Box<dyn Concept> {
-        match &self.to_string() == &concept_old.to_string() {
-            true => concept_new,
-            false => Box::new(ConjunctionConcept {
-                subconcepts: self.subconcepts.clone()
-                    .iter()
-                    .map(|c| c.replace_concept(concept_old.clone(), concept_new.clone()))
-                    .collect::<Vec<Box<dyn Concept>>>()
-            }) as Box<dyn Concept>
-        }
+    fn children(&self) -> Vec<&dyn Concept> {
+        self.subconcepts.iter().map(|c| &**c).collect()
     }
 }
 
 impl fmt::Display for ConjunctionConcept {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         write!(fmt, "and {}", self.subconcepts.iter()
-            .map(|sc| format!("({})", sc.to_string())).collect::<Vec<String>>().join(" "))
+            .map(|sc| format!("({})", sc)).collect::<Vec<String>>().join(" "))
     }
 }
 
@@ -399,29 +604,31 @@ pub struct DisjunctionConcept {
 impl fmt::Display for DisjunctionConcept {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         write!(fmt, "or {}", self.subconcepts.iter()
-            .map(|sc| format!("({})", sc.to_string())).collect::<Vec<String>>().join(" "))
+            .map(|sc| format!("({})", sc)).collect::<Vec<String>>().join(" "))
     }
 }
 
 impl Concept for DisjunctionConcept {
     fn convert_to_nnf(&self) -> Box<dyn Concept> {
-        Box::new(DisjunctionConcept {
-            subconcepts: self.subconcepts.iter().map(|c| { c.convert_to_nnf() }).collect()
-        })
+        // ⊥ can be dropped from a disjunction, while ⊤ turns the whole disjunction into ⊤
+        let mut subconcepts = self.subconcepts.iter()
+            .map(|c| c.convert_to_nnf())
+            .filter(|c| c.concept_type() != ConceptType::Bottom)
+            .collect::<Vec<Box<dyn Concept>>>();
+
+        if subconcepts.iter().any(|c| c.concept_type() == ConceptType::Top) {
+            Box::new(TopConcept)
+        } else if subconcepts.len() > 1 {
+            Box::new(DisjunctionConcept { subconcepts })
+        } else {
+            subconcepts.pop().unwrap_or_else(|| Box::new(BottomConcept))
+        }
     }
 
     fn concept_type(&self) -> ConceptType { ConceptType::Disjunction }
 
-    fn replace_concept(&self, concept_old: Box<dyn Concept>, concept_new: Box<dyn Concept>) -> Box<dyn Concept> {
-        match &self.to_string() == &concept_old.to_string() {
-            true => concept_new,
-            false => Box::new(DisjunctionConcept {
-                subconcepts: self.subconcepts.clone()
-                    .iter()
-                    .map(|c| c.replace_concept(concept_old.clone(), concept_new.clone()))
-                    .collect::<Vec<Box<dyn Concept>>>()
-            }) as Box<dyn Concept>
-        }
+    fn children(&self) -> Vec<&dyn Concept> {
+        self.subconcepts.iter().map(|c| &**c).collect()
     }
 }
 
@@ -441,20 +648,14 @@ impl Concept for OnlyConcept {
 
     fn concept_type(&self) -> ConceptType { ConceptType::Only }
 
-    fn replace_concept(&self, concept_old: Box<dyn Concept>, concept_new: Box<dyn Concept>) -> Box<dyn Concept> {
-        match self.to_string() == concept_old.to_string() {
-            true => concept_new,
-            false => Box::new(OnlyConcept {
-                relation: self.relation.clone(),
-                subconcept: self.subconcept.replace_concept(concept_old, concept_new)
-            }) as Box<dyn Concept>
-        }
+    fn children(&self) -> Vec<&dyn Concept> {
+        vec![&*self.subconcept]
     }
 }
 
 impl fmt::Display for OnlyConcept {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        write!(fmt, "only {} ({})", self.relation.name, self.subconcept)
+        write!(fmt, "only {} ({})", self.relation, self.subconcept)
     }
 }
 
@@ -474,20 +675,14 @@ impl Concept for SomeConcept {
 
     fn concept_type(&self) -> ConceptType { ConceptType::Some }
 
-    fn replace_concept(&self, concept_old: Box<dyn Concept>, concept_new: Box<dyn Concept>) -> Box<dyn Concept> {
-        match self.to_string() == concept_old.to_string() {
-            true => concept_new,
-            false => Box::new(SomeConcept {
-                relation: self.relation.clone(),
-                subconcept: self.subconcept.replace_concept(concept_old, concept_new)
-            }) as Box<dyn Concept>
-        }
+    fn children(&self) -> Vec<&dyn Concept> {
+        vec![&*self.subconcept]
     }
 }
 
 impl fmt::Display for SomeConcept {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        write!(fmt, "some {} ({})", self.relation.name, self.subconcept)
+        write!(fmt, "some {} ({})", self.relation, self.subconcept)
     }
 }
 
@@ -509,21 +704,14 @@ impl Concept for AtLeastConcept {
 
     fn concept_type(&self) -> ConceptType { ConceptType::AtLeast }
 
-    fn replace_concept(&self, concept_old: Box<dyn Concept>, concept_new: Box<dyn Concept>) -> Box<dyn Concept> {
-        match self.to_string() == concept_old.to_string() {
-            true => concept_new,
-            false => Box::new(AtLeastConcept {
-                amount: self.amount,
-                relation: self.relation.clone(),
-                subconcept: self.subconcept.replace_concept(concept_old, concept_new)
-            }) as Box<dyn Concept>
-        }
+    fn children(&self) -> Vec<&dyn Concept> {
+        vec![&*self.subconcept]
     }
 }
 
 impl fmt::Display for AtLeastConcept {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        write!(fmt, ">= {} {} ({})", self.amount, self.relation.name, self.subconcept)
+        write!(fmt, ">= {} {} ({})", self.amount, self.relation, self.subconcept)
     }
 }
 
@@ -545,31 +733,80 @@ impl Concept for AtMostConcept {
 
     fn concept_type(&self) -> ConceptType { ConceptType::AtMost }
 
-    fn replace_concept(&self, concept_old: Box<dyn Concept>, concept_new: Box<dyn Concept>) -> Box<dyn Concept> {
-        match self.to_string() == concept_old.to_string() {
-            true => concept_new,
-            false => Box::new(AtMostConcept {
-                amount: self.amount,
-                relation: self.relation.clone(),
-                subconcept: self.subconcept.replace_concept(concept_old, concept_new)
-            }) as Box<dyn Concept>
-        }
+    fn children(&self) -> Vec<&dyn Concept> {
+        vec![&*self.subconcept]
     }
 }
 
 impl fmt::Display for AtMostConcept {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        write!(fmt, "<= {} {} ({})", self.amount, self.relation.name, self.subconcept)
+        write!(fmt, "<= {} {} ({})", self.amount, self.relation, self.subconcept)
     }
 }
 
 
+#[derive(Debug, Clone, Hash)]
+pub struct NominalConcept { pub individuals: Vec<Individual> }
+
+impl Concept for NominalConcept {
+    fn convert_to_nnf(&self) -> Box<dyn Concept> {
+        Box::new(self.clone())
+    }
+
+    fn concept_type(&self) -> ConceptType { ConceptType::Nominal }
+}
+
+impl fmt::Display for NominalConcept {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let names = self.individuals.iter().map(|x| x.name.as_str()).collect::<Vec<&str>>();
+
+        match names.len() {
+            1 => write!(fmt, "{{{}}}", names[0]),
+            _ => write!(fmt, "oneOf ({})", names.join(" "))
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_extract_concepts() {
-        assert_eq!(extract_concepts("C"), vec![AtomicConcept {name: "C"}]);
+        assert_eq!(extract_concepts("C", "C").unwrap(), vec![Box::new(AtomicConcept {name: "C".to_string()}) as Box<dyn Concept>]);
+    }
+
+    #[test]
+    fn test_parse_concept() {
+        assert_eq!(parse_concept("and (Order (not Android))").unwrap().to_string(), "and (Order) (not Android)");
+        assert_eq!(parse_concept("(>= 2 r (some s A))").unwrap().to_string(), ">= 2 r (some s (A))");
+        assert_eq!(parse_concept("and ({joe} (oneOf (red, green)) {a b})").unwrap().to_string(), "and ({joe}) (oneOf (red green)) (oneOf (a b))");
+        assert_eq!(parse_concept("= 2 r A").unwrap().to_string(), "and (>= 2 r (A)) (<= 2 r (A))");
+    }
+
+    #[test]
+    fn test_top_and_bottom_nnf() {
+        let nnf = |s: &str| parse_concept(s).unwrap().convert_to_nnf().to_string();
+
+        assert_eq!(nnf("not __TOP__"), "__BOTTOM__");
+        assert_eq!(nnf("not ⊥"), "__TOP__");
+        assert_eq!(nnf("and (A __TOP__)"), "A");
+        assert_eq!(nnf("and (A (or (B __BOTTOM__)) C)"), "and (A) (B) (C)");
+        assert_eq!(nnf("and (A (not (or (B __TOP__))))"), "__BOTTOM__");
+        assert_eq!(nnf("or (A (not __BOTTOM__))"), "__TOP__");
+        assert_eq!(nnf("not (>= 0 r A)"), "__BOTTOM__");
+    }
+
+    #[test]
+    fn test_parse_concept_errors() {
+        let error = parse_concept("and (A (some r) B)").unwrap_err();
+        assert_eq!((error.column, error.token.as_str()), (15, ""));
+        assert_eq!(error.expected, "a concept");
+
+        let error = parse_concept("<= many r A").unwrap_err();
+        assert_eq!((error.column, error.token.as_str()), (4, "many"));
+
+        let error = parse_concept("or (A not B)").unwrap_err();
+        assert_eq!((error.column, error.token.as_str()), (7, "not"));
     }
 }
@@ -0,0 +1,159 @@
+/*
+    Concept factory interns concepts into a DAG: every distinct (sub)concept gets an integer id
+    and equal subconcepts are shared. The tableau works with these ids, so label membership,
+    clash detection and blocking compare integers instead of formatting whole concept trees.
+    Negations (in NNF) are computed once per concept and cached.
+    Boxed concepts are built back from the ids only for the output.
+*/
+use std::collections::HashMap;
+
+use concept::*;
+
+
+pub type ConceptId = usize;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ConceptNode {
+    Top,
+    Bottom,
+    Atomic(String),
+    Nominal(Vec<Individual>),
+    Not(ConceptId),
+    And(Vec<ConceptId>),
+    Or(Vec<ConceptId>),
+    Some(Relation, ConceptId),
+    Only(Relation, ConceptId),
+    AtLeast(usize, Relation, ConceptId),
+    AtMost(usize, Relation, ConceptId)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ConceptFactory {
+    nodes: Vec<ConceptNode>,
+    ids: HashMap<ConceptNode, ConceptId>,
+    negations: HashMap<ConceptId, ConceptId>
+}
+
+impl ConceptFactory {
+    pub fn new() -> ConceptFactory {
+        ConceptFactory::default()
+    }
+
+    pub fn intern(&mut self, concept: &dyn Concept) -> ConceptId {
+        let node = match concept.concept_type() {
+            ConceptType::Top => ConceptNode::Top,
+            ConceptType::Bottom => ConceptNode::Bottom,
+            ConceptType::Atomic => ConceptNode::Atomic(concept.downcast_ref::<AtomicConcept>().unwrap().name.clone()),
+            ConceptType::Nominal => ConceptNode::Nominal(concept.downcast_ref::<NominalConcept>().unwrap().individuals.clone()),
+            ConceptType::Not => ConceptNode::Not(self.intern(&*concept.downcast_ref::<NotConcept>().unwrap().subconcept)),
+            ConceptType::Conjunction => ConceptNode::And(concept.downcast_ref::<ConjunctionConcept>().unwrap().subconcepts
+                .iter().map(|c| self.intern(&**c)).collect()),
+            ConceptType::Disjunction => ConceptNode::Or(concept.downcast_ref::<DisjunctionConcept>().unwrap().subconcepts
+                .iter().map(|c| self.intern(&**c)).collect()),
+            ConceptType::Some => {
+                let c = concept.downcast_ref::<SomeConcept>().unwrap();
+                ConceptNode::Some(c.relation.clone(), self.intern(&*c.subconcept))
+            },
+            ConceptType::Only => {
+                let c = concept.downcast_ref::<OnlyConcept>().unwrap();
+                ConceptNode::Only(c.relation.clone(), self.intern(&*c.subconcept))
+            },
+            ConceptType::AtLeast => {
+                let c = concept.downcast_ref::<AtLeastConcept>().unwrap();
+                ConceptNode::AtLeast(c.amount, c.relation.clone(), self.intern(&*c.subconcept))
+            },
+            ConceptType::AtMost => {
+                let c = concept.downcast_ref::<AtMostConcept>().unwrap();
+                ConceptNode::AtMost(c.amount, c.relation.clone(), self.intern(&*c.subconcept))
+            }
+        };
+
+        self.intern_node(node)
+    }
+
+    pub fn intern_node(&mut self, node: ConceptNode) -> ConceptId {
+        if let Some(&id) = self.ids.get(&node) {
+            return id;
+        }
+
+        let id = self.nodes.len();
+        self.nodes.push(node.clone());
+        self.ids.insert(node, id);
+        id
+    }
+
+    pub fn find(&self, node: &ConceptNode) -> Option<ConceptId> {
+        // Unlike `intern`, does not create anything: a concept, which was never interned, cannot be in a label
+        self.ids.get(node).cloned()
+    }
+
+    pub fn node(&self, id: ConceptId) -> &ConceptNode {
+        &self.nodes[id]
+    }
+
+    pub fn negation(&mut self, id: ConceptId) -> ConceptId {
+        // NNF of ¬C, simplified the same way as `convert_to_nnf` does it
+        if let Some(&negation) = self.negations.get(&id) {
+            return negation;
+        }
+
+        let negation = self.intern(&*self.concept(id).negate().convert_to_nnf());
+        self.negations.insert(id, negation);
+        negation
+    }
+
+    pub fn concept(&self, id: ConceptId) -> Box<dyn Concept> {
+        match self.nodes[id] {
+            ConceptNode::Top => Box::new(TopConcept),
+            ConceptNode::Bottom => Box::new(BottomConcept),
+            ConceptNode::Atomic(ref name) => Box::new(AtomicConcept { name: name.clone() }),
+            ConceptNode::Nominal(ref individuals) => Box::new(NominalConcept { individuals: individuals.clone() }),
+            ConceptNode::Not(c) => Box::new(NotConcept { subconcept: self.concept(c) }),
+            ConceptNode::And(ref cs) => Box::new(ConjunctionConcept { subconcepts: cs.iter().map(|&c| self.concept(c)).collect() }),
+            ConceptNode::Or(ref cs) => Box::new(DisjunctionConcept { subconcepts: cs.iter().map(|&c| self.concept(c)).collect() }),
+            ConceptNode::Some(ref relation, c) => Box::new(SomeConcept { relation: relation.clone(), subconcept: self.concept(c) }),
+            ConceptNode::Only(ref relation, c) => Box::new(OnlyConcept { relation: relation.clone(), subconcept: self.concept(c) }),
+            ConceptNode::AtLeast(amount, ref relation, c) => Box::new(AtLeastConcept {
+                amount,
+                relation: relation.clone(),
+                subconcept: self.concept(c)
+            }),
+            ConceptNode::AtMost(amount, ref relation, c) => Box::new(AtMostConcept {
+                amount,
+                relation: relation.clone(),
+                subconcept: self.concept(c)
+            })
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interning() {
+        let mut factory = ConceptFactory::new();
+        let concept = parse_concept("and ((some r (and (A B))) (only s (and (A B))) (not C))").unwrap();
+        let id = factory.intern(&*concept);
+
+        assert_eq!(factory.intern(&*parse_concept("and ((some r (and (A B))) (only s (and (A B))) (not C))").unwrap()), id);
+        assert_eq!(factory.concept(id).to_string(), concept.to_string());
+
+        // and (A B) is shared by both of the quantifiers
+        let shared = factory.find(&ConceptNode::And(vec![
+            factory.find(&ConceptNode::Atomic("A".to_string())).unwrap(),
+            factory.find(&ConceptNode::Atomic("B".to_string())).unwrap()
+        ])).unwrap();
+        assert_eq!(factory.node(id), &ConceptNode::And(vec![
+            factory.find(&ConceptNode::Some(Relation::new("r"), shared)).unwrap(),
+            factory.find(&ConceptNode::Only(Relation::new("s"), shared)).unwrap(),
+            factory.find(&ConceptNode::Not(factory.find(&ConceptNode::Atomic("C".to_string())).unwrap())).unwrap()
+        ]));
+
+        let negation = factory.negation(id);
+        assert_eq!(factory.concept(negation).to_string(), concept.negate().convert_to_nnf().to_string());
+        assert_eq!(factory.negation(negation), id);
+    }
+}
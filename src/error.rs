@@ -0,0 +1,137 @@
+use std::fmt;
+use std::error;
+
+
+/// An error found while parsing an ABox/TBox file or a single concept.
+/// It points to the offending token and knows how to render itself compiler-style:
+///
+/// ```text
+/// error: expected a relation name, found `)`
+///  --> tbox.txt:3:11
+///   |
+/// 3 | A == some ) B
+///   |           ^
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub file: Option<String>,
+    pub line: usize,
+    pub column: usize,
+    pub token: String,
+    pub expected: String,
+    pub source_line: String
+}
+
+impl ParseError {
+    /// Creates an error for `token`, which must be a slice of `source`
+    /// (that's how we find out the column without passing offsets around).
+    pub fn new(source: &str, token: &str, expected: &str) -> ParseError {
+        let offset = (token.as_ptr() as usize)
+            .checked_sub(source.as_ptr() as usize)
+            .filter(|&o| o <= source.len() && source.is_char_boundary(o))
+            .unwrap_or(0);
+
+        ParseError {
+            file: None,
+            line: 1,
+            column: source[..offset].chars().count() + 1,
+            token: token.to_string(),
+            expected: expected.to_string(),
+            source_line: source.to_string()
+        }
+    }
+
+    pub fn at_line(mut self, line: usize) -> ParseError {
+        self.line = line;
+        self
+    }
+
+    pub fn in_file(mut self, file: &str) -> ParseError {
+        self.file = Some(file.to_string());
+        self
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let found = if self.token.is_empty() { "nothing".to_string() } else { format!("`{}`", self.token) };
+        let location = format!("{}:{}:{}", self.file.as_ref().map_or("<input>", |f| f.as_str()), self.line, self.column);
+        let gutter = " ".repeat(self.line.to_string().len());
+        let padding = " ".repeat(self.column - 1);
+        let carets = "^".repeat(self.token.chars().count().max(1));
+
+        writeln!(fmt, "error: expected {}, found {}", self.expected, found)?;
+        writeln!(fmt, "{}--> {}", gutter, location)?;
+        writeln!(fmt, "{} |", gutter)?;
+        writeln!(fmt, "{} | {}", self.line, self.source_line)?;
+        write!(fmt, "{} | {}{}", gutter, padding, carets)
+    }
+}
+
+impl error::Error for ParseError {}
+
+
+/// A TBox, which defines some concepts through each other (e.g. "A == some r B" and "B == only r A").
+/// `cycle` starts and ends with the same concept name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CyclicDefinitionError {
+    pub cycle: Vec<String>
+}
+
+impl fmt::Display for CyclicDefinitionError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(fmt, "error: cyclic definitions: {}", self.cycle.join(" -> "))?;
+        write!(fmt, "  = note: each concept is used in the definition of the previous one")
+    }
+}
+
+impl error::Error for CyclicDefinitionError {}
+
+
+/// An at-least/at-most concept over a relation, which is transitive or has a transitive subrelation
+/// (i.e. is not simple): reasoning with such restrictions is undecidable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NonSimpleRelationError {
+    pub relation: String,
+    pub concept: String
+}
+
+impl fmt::Display for NonSimpleRelationError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(fmt, "error: relation `{}` is not simple, but it is restricted in `{}`", self.relation, self.concept)?;
+        write!(fmt, "  = note: number restrictions on transitive relations (and their superrelations) are not supported")
+    }
+}
+
+impl error::Error for NonSimpleRelationError {}
+
+
+/// Why a reasoner cannot be created for a knowledge base
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KnowledgeBaseError {
+    CyclicDefinition(CyclicDefinitionError),
+    NonSimpleRelation(NonSimpleRelationError)
+}
+
+impl From<CyclicDefinitionError> for KnowledgeBaseError {
+    fn from(error: CyclicDefinitionError) -> KnowledgeBaseError {
+        KnowledgeBaseError::CyclicDefinition(error)
+    }
+}
+
+impl From<NonSimpleRelationError> for KnowledgeBaseError {
+    fn from(error: NonSimpleRelationError) -> KnowledgeBaseError {
+        KnowledgeBaseError::NonSimpleRelation(error)
+    }
+}
+
+impl fmt::Display for KnowledgeBaseError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KnowledgeBaseError::CyclicDefinition(e) => write!(fmt, "{}", e),
+            KnowledgeBaseError::NonSimpleRelation(e) => write!(fmt, "{}", e)
+        }
+    }
+}
+
+impl error::Error for KnowledgeBaseError {}
@@ -0,0 +1,42 @@
+use std::fmt;
+
+use abox::{ABox, parse_abox};
+use tbox::{TBox, parse_tbox};
+use error::ParseError;
+
+
+#[derive(Debug, Clone)]
+pub struct KnowledgeBase {
+    pub abox: ABox,
+    pub tbox: TBox,
+    // Cyclic definitions are treated as GCIs, otherwise the reasoner rejects them
+    pub allow_cyclic_definitions: bool
+}
+
+impl KnowledgeBase {
+    pub fn new(abox: ABox, tbox: TBox) -> KnowledgeBase {
+        KnowledgeBase { abox, tbox, allow_cyclic_definitions: false }
+    }
+
+    pub fn with_cyclic_definitions(mut self, allow_cyclic_definitions: bool) -> KnowledgeBase {
+        self.allow_cyclic_definitions = allow_cyclic_definitions;
+        self
+    }
+
+    pub fn parse(abox_str: &str, tbox_str: &str) -> Result<KnowledgeBase, Vec<ParseError>> {
+        match (parse_abox(abox_str), parse_tbox(tbox_str)) {
+            (Ok(abox), Ok(tbox)) => Ok(KnowledgeBase::new(abox, tbox)),
+            (abox, tbox) => Err(abox.err().into_iter().chain(tbox.err()).flatten().collect())
+        }
+    }
+
+    pub fn from_tbox(tbox: TBox) -> KnowledgeBase {
+        KnowledgeBase::new(ABox::new(), tbox)
+    }
+}
+
+impl fmt::Display for KnowledgeBase {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}\n{}", self.abox, self.tbox)
+    }
+}
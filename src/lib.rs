@@ -0,0 +1,22 @@
+// `mopafy!` downcasts through raw pointer transmutes, which we cannot change from here
+#![allow(clippy::transmute_ptr_to_ref)]
+
+#[macro_use] extern crate mopa;
+#[macro_use] extern crate log;
+
+pub mod abox;
+pub mod tbox;
+pub mod rbox;
+pub mod concept;
+pub mod reasoner;
+pub mod concept_factory;
+pub mod satisfiability_cache;
+pub mod pseudo_model;
+pub mod told_subsumers;
+pub mod completion_graph;
+pub mod taxonomy;
+pub mod knowledge_base;
+pub mod error;
+
+pub use knowledge_base::KnowledgeBase;
+pub use reasoner::{Reasoner, ExpansionStrategy, TableauStatistics};
@@ -1,17 +1,32 @@
-#![allow(unused)]
 use std::env;
 use std::fs;
-use std::time::{Duration, Instant};
+use std::io::{self, Read};
+use std::process;
+use std::time::Instant;
 
-#[macro_use] extern crate mopa;
 #[macro_use] extern crate log;
 extern crate fern;
 extern crate chrono;
+extern crate dl_reasoner;
 
-mod abox;
-mod tbox;
-mod concept;
-mod reasoner;
+use dl_reasoner::{abox, tbox, concept, KnowledgeBase, Reasoner, ExpansionStrategy};
+use dl_reasoner::error::{ParseError, KnowledgeBaseError};
+
+
+const USAGE: &str = "usage: dl-reasoner <command> [options]
+
+commands:
+    check-consistency <abox> <tbox> [--una]
+    check-subsumption --tbox <tbox> <subsumption>
+    check-subsumption --tbox <tbox> --queries <file>
+    check-subsumption <tbox-with-subsumptions>
+    check-satisfiability <tbox> [<concept>]
+    classify <tbox>
+    instances <abox> <tbox> <concept>
+    realize <abox> <tbox>
+
+options (for any command):
+    --cyclic-as-gcis, --deterministic-first, --smallest-disjunction-first";
 
 
 fn main() {
@@ -34,7 +49,8 @@ fn main() {
         // .level(log::LevelFilter::Debug)
         .chain(std::io::stdout())
         // .chain(fern::log_file("output.log"))
-        .apply();
+        .apply()
+        .unwrap();
 
     let start = Instant::now();
     run_reasoner();
@@ -43,65 +59,222 @@ fn main() {
 }
 
 fn run_reasoner() {
-    let args: Vec<String> = env::args().collect();
-    let command = &args[1];
+    let mut args: Vec<String> = env::args().collect();
+    // Cyclic definitions are rejected, unless we are asked to treat them as GCIs
+    let cyclic_as_gcis = args.iter().any(|a| a == "--cyclic-as-gcis");
+    args.retain(|a| a != "--cyclic-as-gcis");
+    // The order, in which the tableau applies the rules
+    let strategy = ExpansionStrategy {
+        deterministic_first: args.iter().any(|a| a == "--deterministic-first"),
+        smallest_disjunction_first: args.iter().any(|a| a == "--smallest-disjunction-first")
+    };
+    args.retain(|a| a != "--deterministic-first" && a != "--smallest-disjunction-first");
+    let command = argument(&args, 1, "command");
 
-    match command.as_str() {
+    match command {
         "check-consistency" => {
-            let abox_filename = &args[2];
-            let tbox_filename = &args[3];
+            let kb = load_knowledge_base(argument(&args, 2, "ABox file"), argument(&args, 3, "TBox file"));
+            let unique_name_assumption = args.iter().skip(4).any(|a| a == "--una");
 
-            let abox_file_contents = fs::read_to_string(abox_filename).unwrap();
-            let tbox_file_contents = fs::read_to_string(tbox_filename).unwrap();
+            let reasoner = create_reasoner(kb, cyclic_as_gcis).with_strategy(strategy).with_unique_name_assumption(unique_name_assumption);
 
-            let mut abox = abox::parse_abox(&abox_file_contents);
-            debug!("Initial abox: {}", abox);
+            match reasoner.find_model() {
+                None => info!("No model was found."),
+                Some(model) => {
+                    info!("Found a model!");
+                    info!("{}", model);
+                }
+            }
 
-            let mut tbox = tbox::parse_tbox(&tbox_file_contents);
+            info!("Tableau statistics: {}", reasoner.statistics());
+        },
+        "check-subsumption" => {
+            let (tbox, subsumptions) = match argument(&args, 2, "TBox file") {
+                "--tbox" => {
+                    // Background TBox and the queries (either a single one or a file of them) are separate
+                    let tbox_filename = argument(&args, 3, "TBox file");
+                    let tbox = tbox::parse_tbox(&read_file(tbox_filename))
+                        .unwrap_or_else(|es| exit_with_parse_errors(in_file(es, tbox_filename)));
+                    let subsumptions = match argument(&args, 4, "subsumption") {
+                        "--queries" => {
+                            let queries_filename = argument(&args, 5, "file of subsumptions");
+                            tbox::parse_subsumption_queries(&read_file(queries_filename))
+                                .unwrap_or_else(|es| exit_with_parse_errors(in_file(es, queries_filename)))
+                        },
+                        subsumption => tbox::parse_subsumption_queries(subsumption)
+                            .unwrap_or_else(|es| exit_with_parse_errors(es))
+                    };
+
+                    (tbox, subsumptions)
+                },
+                tbox_filename => {
+                    // All the GCIs in the file are the subsumptions we should check
+                    let tbox_file_contents = read_file(tbox_filename);
+                    let mut tbox = tbox::parse_tbox(&tbox_file_contents)
+                        .unwrap_or_else(|es| exit_with_parse_errors(in_file(es, tbox_filename)));
+                    let subsumptions = tbox.take_inclusions();
+
+                    (tbox, subsumptions)
+                }
+            };
             debug!("Initial tbox: {}", tbox);
 
-            tbox.expand_all_definitions();
-            tbox.apply_definitions_to_abox(&mut abox);
-            tbox.apply_definitions_to_inclusions();
-            let super_gci = tbox.aggregate_inclusions().map(|c| Box::new(c) as Box<dyn concept::Concept>);
+            if subsumptions.is_empty() {
+                eprintln!("error: you have not provided a subsumption to check");
+                process::exit(1);
+            }
 
-            debug!("Abox after definitions applied: {}", abox);
+            let reasoner = create_reasoner(KnowledgeBase::from_tbox(tbox), cyclic_as_gcis).with_strategy(strategy);
 
-            match reasoner::tableau_reasoning(abox, super_gci) {
-                None => info!("No model was found."),
-                Some(a) => {
-                    info!("Found a model!");
-                    info!("{}", a.extract_model());
+            for subsumption in &subsumptions {
+                match reasoner.find_subsumption_counter_model(&*subsumption.lhs, &*subsumption.rhs) {
+                    None => info!("Subsumption {} is valid.", subsumption),
+                    Some(model) => {
+                        info!("Subsumption {} is not valid.", subsumption);
+                        info!("Here is the model of its controversial: {}", model);
+                    }
                 }
             }
         },
-        "check-subsumption" => {
-            // Initialzing TBox
-            let tbox_filename = &args[2];
-            let tbox_file_contents = fs::read_to_string(tbox_filename).unwrap();
-            let mut tbox = tbox::parse_tbox(&tbox_file_contents);
+        "check-satisfiability" => {
+            let tbox_filename = argument(&args, 2, "TBox file");
+            let tbox_file_contents = read_file(tbox_filename);
+            let tbox = tbox::parse_tbox(&tbox_file_contents)
+                .unwrap_or_else(|es| exit_with_parse_errors(in_file(es, tbox_filename)));
             debug!("Initial tbox: {}", tbox);
 
-            tbox.expand_all_definitions();
-            tbox.apply_definitions_to_inclusions();
-            let super_gci = tbox.aggregate_inclusions();
-            assert!(super_gci.is_some(), "Error: you have not provided a subsumption to check!");
-
-            // Initialzing ABox
-            let mut abox = abox::ABox::new();
-            let x = concept::Individual {name: "a".to_string()};
-            let subsumption = Box::new(super_gci.clone().unwrap()) as Box<dyn concept::Concept>;
-            let subsumption_negated = subsumption.negate().convert_to_nnf();
-            abox.add_individual(x.clone());
-
-            match reasoner::tableau_reasoning(abox, Some(subsumption_negated)) {
-                None => info!("Subsumption is valid."),
-                Some(a) => {
-                    info!("Subsumption is not valid.");
-                    info!("Here is the model of its controversial: {}", a.extract_model());
+            // The concept is either the next argument or the whole stdin
+            let concept_str = match args.get(3) {
+                Some(concept_str) => concept_str.clone(),
+                None => {
+                    let mut concept_str = String::new();
+                    io::stdin().read_to_string(&mut concept_str).unwrap_or_else(|e| exit_with_io_error("stdin", e));
+                    concept_str
+                }
+            };
+            let concept = concept::parse_concept(&concept_str).unwrap_or_else(|e| exit_with_parse_errors(vec![e]));
+
+            let reasoner = create_reasoner(KnowledgeBase::from_tbox(tbox), cyclic_as_gcis).with_strategy(strategy);
+
+            match reasoner.find_concept_model(&*concept) {
+                None => info!("Concept is unsatisfiable."),
+                Some(model) => {
+                    info!("Concept is satisfiable.");
+                    info!("Here is a model, where individual `a` belongs to the concept: {}", model);
                 }
             }
+
+            info!("Tableau statistics: {}", reasoner.statistics());
         },
-        _ => panic!("Error: unknown command: {}", command)
+        "classify" => {
+            let tbox_filename = argument(&args, 2, "TBox file");
+            let tbox_file_contents = read_file(tbox_filename);
+            let tbox = tbox::parse_tbox(&tbox_file_contents)
+                .unwrap_or_else(|es| exit_with_parse_errors(in_file(es, tbox_filename)));
+            debug!("Initial tbox: {}", tbox);
+
+            let reasoner = create_reasoner(KnowledgeBase::from_tbox(tbox), cyclic_as_gcis).with_strategy(strategy);
+            let taxonomy = reasoner.classify();
+
+            info!("{}", taxonomy);
+            info!("Number of subsumption tests performed: {}", taxonomy.num_subsumption_tests);
+            info!("Tableau statistics: {}", reasoner.statistics());
+        },
+        "instances" => {
+            let kb = load_knowledge_base(argument(&args, 2, "ABox file"), argument(&args, 3, "TBox file"));
+            let concept = concept::parse_concept(argument(&args, 4, "concept")).unwrap_or_else(|e| exit_with_parse_errors(vec![e]));
+            let reasoner = create_reasoner(kb, cyclic_as_gcis).with_strategy(strategy);
+
+            if !reasoner.is_consistent() {
+                info!("The knowledge base is inconsistent, so every individual is an instance of every concept.");
+                return;
+            }
+
+            let instances = reasoner.instances(&*concept);
+            info!("Instances of {}: {}", concept, instances.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(", "));
+        },
+        "realize" => {
+            let kb = load_knowledge_base(argument(&args, 2, "ABox file"), argument(&args, 3, "TBox file"));
+            let reasoner = create_reasoner(kb, cyclic_as_gcis).with_strategy(strategy);
+
+            if !reasoner.is_consistent() {
+                info!("The knowledge base is inconsistent, so every individual is an instance of every concept.");
+                return;
+            }
+
+            for (x, types) in reasoner.realize() {
+                info!("{}: {}", x, types.join(", "));
+            }
+        },
+        _ => exit_with_usage(&format!("unknown command `{}`", command))
     }
 }
+
+fn argument<'a>(args: &'a [String], index: usize, name: &str) -> &'a str {
+    // The positional arguments are required, so a missing one is a usage error
+    args.get(index).map(|a| a.as_str()).unwrap_or_else(|| exit_with_usage(&format!("missing {}", name)))
+}
+
+fn read_file(filename: &str) -> String {
+    fs::read_to_string(filename).unwrap_or_else(|e| exit_with_io_error(filename, e))
+}
+
+fn load_knowledge_base(abox_filename: &str, tbox_filename: &str) -> KnowledgeBase {
+    let abox_file_contents = read_file(abox_filename);
+    let tbox_file_contents = read_file(tbox_filename);
+
+    let abox = abox::parse_abox(&abox_file_contents).map_err(|es| in_file(es, abox_filename));
+    let tbox = tbox::parse_tbox(&tbox_file_contents).map_err(|es| in_file(es, tbox_filename));
+
+    let kb = match (abox, tbox) {
+        (Ok(abox), Ok(tbox)) => KnowledgeBase::new(abox, tbox),
+        (abox, tbox) => exit_with_parse_errors(abox.err().into_iter().chain(tbox.err()).flatten().collect())
+    };
+    debug!("Initial knowledge base: {}", kb);
+
+    kb
+}
+
+fn create_reasoner(kb: KnowledgeBase, cyclic_as_gcis: bool) -> Reasoner {
+    let reasoner = Reasoner::new(kb.with_cyclic_definitions(cyclic_as_gcis)).unwrap_or_else(|e| {
+        match e {
+            KnowledgeBaseError::CyclicDefinition(e) => {
+                eprintln!("{}\n  = help: run with `--cyclic-as-gcis` to treat the cyclic definitions as GCIs", e)
+            },
+            KnowledgeBaseError::NonSimpleRelation(e) => eprintln!("{}", e)
+        }
+
+        process::exit(1);
+    });
+    let (num_absorbed, num_inclusions) = reasoner.num_absorbed_inclusions();
+
+    if num_inclusions > 0 {
+        info!("Absorbed {} of {} GCIs into lazy unfolding rules", num_absorbed, num_inclusions);
+    }
+
+    reasoner
+}
+
+fn in_file(errors: Vec<ParseError>, filename: &str) -> Vec<ParseError> {
+    errors.into_iter().map(|e| e.in_file(filename)).collect()
+}
+
+fn exit_with_parse_errors(errors: Vec<ParseError>) -> ! {
+    // Prints all the parse errors compiler-style and stops the program
+    for e in &errors {
+        eprintln!("{}\n", e);
+    }
+
+    eprintln!("error: aborting due to {} parse error(s)", errors.len());
+    process::exit(1);
+}
+
+fn exit_with_io_error(source: &str, error: io::Error) -> ! {
+    eprintln!("error: cannot read `{}`: {}", source, error);
+    process::exit(1);
+}
+
+fn exit_with_usage(message: &str) -> ! {
+    eprintln!("error: {}\n\n{}", message, USAGE);
+    process::exit(1);
+}
@@ -0,0 +1,94 @@
+/*
+    Pseudo-model of a satisfiable concept sums up the root of its complete tableau:
+    the concept names, which are (or are negated) in the root label, and the relations,
+    which the root has successors for ("some" and "at-least") or restricts the successors of ("only" and "at-most").
+    If the pseudo-models of C and D do not conflict, the roots of their models can be merged into a model of "and (C D)",
+    e.g. during classification the pseudo-models of C and ¬D prove, that C is not subsumed by D, without running the tableau.
+    This is fine only without inverse relations and nominals, since then nothing flows back to a root from the successors.
+*/
+use std::collections::HashSet;
+
+use concept::Relation;
+use concept_factory::{ConceptFactory, ConceptId, ConceptNode};
+use rbox::RBox;
+
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PseudoModel {
+    concepts: HashSet<ConceptId>,
+    // Concept names, which negations are in the label
+    negated_concepts: HashSet<ConceptId>,
+    some_relations: HashSet<Relation>,
+    only_relations: HashSet<Relation>
+}
+
+impl PseudoModel {
+    pub fn new(factory: &ConceptFactory, label: &[ConceptId], rbox: &RBox) -> PseudoModel {
+        let mut model = PseudoModel::default();
+
+        for &c in label {
+            match factory.node(c) {
+                ConceptNode::Atomic(_) => { model.concepts.insert(c); },
+                ConceptNode::Not(a) => { model.negated_concepts.insert(*a); },
+                ConceptNode::Some(r, _) => { model.some_relations.insert(r.clone()); },
+                ConceptNode::AtLeast(n, r, _) if *n > 0 => { model.some_relations.insert(r.clone()); },
+                ConceptNode::Only(r, _) | ConceptNode::AtMost(_, r, _) => { model.only_relations.insert(r.clone()); },
+                _ => {}
+            }
+        }
+
+        // A functional relation restricts the successors as well: the successors of both roots would be merged
+        let functional_relations = rbox.functional_relations()
+            .into_iter()
+            .filter(|s| model.some_relations.iter().any(|r| rbox.is_subrelation(r, s)))
+            .collect::<Vec<Relation>>();
+        model.only_relations.extend(functional_relations);
+
+        model
+    }
+
+    pub fn can_merge(&self, other: &PseudoModel, rbox: &RBox) -> bool {
+        self.concepts.is_disjoint(&other.negated_concepts)
+            && self.negated_concepts.is_disjoint(&other.concepts)
+            && !restricts_successors(self, other, rbox)
+            && !restricts_successors(other, self, rbox)
+    }
+}
+
+
+fn restricts_successors(lhs: &PseudoModel, rhs: &PseudoModel, rbox: &RBox) -> bool {
+    // I.e. a successor of the rhs root would become an s-successor of the lhs root, which has "only s" or "at-most s"
+    rhs.some_relations.iter().any(|r| lhs.only_relations.iter().any(|s| rbox.is_subrelation(r, s)))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use concept::parse_concept;
+
+    #[test]
+    fn test_merging() {
+        let mut factory = ConceptFactory::new();
+        let mut rbox = RBox::new();
+        let mut model = |concepts: &[&str], rbox: &RBox| {
+            let label = concepts.iter().map(|c| factory.intern(&*parse_concept(c).unwrap())).collect::<Vec<ConceptId>>();
+            PseudoModel::new(&factory, &label, rbox)
+        };
+
+        let a = model(&["A", "some r B", "B"], &rbox);
+        assert!(a.can_merge(&model(&["not C", "only s C"], &rbox), &rbox));
+        assert!(!a.can_merge(&model(&["not A"], &rbox), &rbox));
+        assert!(!a.can_merge(&model(&["only r C"], &rbox), &rbox));
+        assert!(!a.can_merge(&model(&["<= 1 r C"], &rbox), &rbox));
+
+        rbox.add_inclusion(Relation::new("r"), Relation::new("s"));
+        let a = model(&["A", "some r B", "B"], &rbox);
+        assert!(!a.can_merge(&model(&["only s C"], &rbox), &rbox));
+
+        rbox.add_functional_relation(Relation::new("s"));
+        let a = model(&["A", "some r B", "B"], &rbox);
+        assert!(!a.can_merge(&model(&["some s C"], &rbox), &rbox));
+        assert!(a.can_merge(&model(&["some t C"], &rbox), &rbox));
+    }
+}
@@ -0,0 +1,242 @@
+/*
+    RBox axioms live in the TBox file and start with a keyword:
+    "role hasSon -> hasChild" (relation inclusion),
+    "role isChildOf == hasChild-" (relation equivalence, i.e. inclusions in both directions),
+    "transitive partOf" (transitivity declaration),
+    "functional hasMother", "inverseFunctional hasSSN" (functionality declarations)
+*/
+use std::fmt;
+use std::collections::{HashSet, HashMap};
+
+use concept::{Concept, Relation, extract_number_restrictions};
+use error::{ParseError, NonSimpleRelationError};
+
+
+pub const RBOX_KEYWORDS: [&str; 4] = ["role", "transitive", "functional", "inverseFunctional"];
+
+
+pub fn add_rbox_axiom(rbox: &mut RBox, axiom_line: &str) -> Result<(), ParseError> {
+    let axiom_str = axiom_line.trim();
+    let keyword_len = axiom_str.find(char::is_whitespace).unwrap_or(axiom_str.len());
+    let rest = axiom_str[keyword_len..].trim();
+
+    match &axiom_str[..keyword_len] {
+        "role" => {
+            let (delimiter, is_equivalence) = if rest.contains("==") {
+                ("==", true)
+            } else if rest.contains("->") {
+                ("->", false)
+            } else {
+                return Err(ParseError::new(axiom_line, rest, "a relation inclusion `r -> s` or an equivalence `r == s`"));
+            };
+            let delimiter_idx = rest.find(delimiter).unwrap();
+            let sub = parse_relation(rest[..delimiter_idx].trim(), axiom_line)?;
+            let sup = parse_relation(rest[delimiter_idx + 2..].trim(), axiom_line)?;
+
+            rbox.add_inclusion(sub.clone(), sup.clone());
+
+            if is_equivalence {
+                rbox.add_inclusion(sup, sub);
+            }
+        },
+        "transitive" => {
+            rbox.add_transitive_relation(parse_relation(rest, axiom_line)?);
+        },
+        "functional" => {
+            rbox.add_functional_relation(parse_relation(rest, axiom_line)?);
+        },
+        "inverseFunctional" => {
+            rbox.add_functional_relation(parse_relation(rest, axiom_line)?.inverse());
+        },
+        _ => return Err(ParseError::new(axiom_line, &axiom_str[..keyword_len], "an RBox keyword"))
+    }
+
+    Ok(())
+}
+
+
+fn parse_relation(relation_str: &str, source: &str) -> Result<Relation, ParseError> {
+    let is_valid = !relation_str.is_empty() && relation_str != "-"
+        && !relation_str.contains(|c: char| c.is_whitespace() || c == '(' || c == ')');
+
+    match is_valid {
+        true => Ok(Relation::new(relation_str)),
+        false => Err(ParseError::new(source, relation_str, "a relation name"))
+    }
+}
+
+
+#[derive(Debug, Clone)]
+pub struct RBox {
+    inclusions: HashSet<(Relation, Relation)>,
+    // Reflexive-transitive closure of the inclusions (reflexivity is implied, i.e. not stored)
+    super_relations: HashMap<Relation, HashSet<Relation>>,
+    transitive_relations: HashSet<Relation>,
+    functional_relations: HashSet<Relation>
+}
+
+impl Default for RBox {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RBox {
+    pub fn new() -> RBox {
+        RBox {
+            inclusions: HashSet::new(),
+            super_relations: HashMap::new(),
+            transitive_relations: HashSet::new(),
+            functional_relations: HashSet::new()
+        }
+    }
+
+    pub fn add_inclusion(&mut self, sub: Relation, sup: Relation) {
+        // r ⊑ s implies r- ⊑ s-, so we store both of them
+        self.inclusions.insert((sub.inverse(), sup.inverse()));
+        self.inclusions.insert((sub, sup));
+        self.compute_hierarchy();
+    }
+
+    fn compute_hierarchy(&mut self) {
+        // We have just a handful of relations, so a simple fixpoint iteration is fine
+        self.super_relations = HashMap::new();
+
+        for (sub, sup) in &self.inclusions {
+            self.super_relations.entry(sub.clone()).or_default().insert(sup.clone());
+        }
+
+        loop {
+            let mut is_changed = false;
+
+            for sub in self.super_relations.keys().cloned().collect::<Vec<Relation>>() {
+                let sups = self.super_relations[&sub].clone();
+                let sups_of_sups = sups.iter()
+                    .filter_map(|s| self.super_relations.get(s))
+                    .flatten()
+                    .filter(|s| !sups.contains(s))
+                    .cloned()
+                    .collect::<Vec<Relation>>();
+
+                if !sups_of_sups.is_empty() {
+                    self.super_relations.get_mut(&sub).unwrap().extend(sups_of_sups);
+                    is_changed = true;
+                }
+            }
+
+            if !is_changed {
+                break;
+            }
+        }
+    }
+
+    pub fn is_subrelation(&self, sub: &Relation, sup: &Relation) -> bool {
+        sub == sup || self.super_relations.get(sub).is_some_and(|sups| sups.contains(sup))
+    }
+
+    pub fn add_transitive_relation(&mut self, relation: Relation) {
+        // r is transitive iff r- is transitive
+        self.transitive_relations.insert(relation.inverse());
+        self.transitive_relations.insert(relation);
+    }
+
+    pub fn is_transitive(&self, relation: &Relation) -> bool {
+        self.transitive_relations.contains(relation)
+    }
+
+    pub fn add_functional_relation(&mut self, relation: Relation) {
+        // Inverse functional relations are stored as functional inverses
+        self.functional_relations.insert(relation);
+    }
+
+    pub fn functional_relations(&self) -> Vec<Relation> {
+        sorted(self.functional_relations.iter().cloned().collect())
+    }
+
+    pub fn has_inverse_inclusions(&self) -> bool {
+        // I.e. r ⊑ s- for some r and s, so that an r-edge is an s-edge in the opposite direction
+        self.inclusions.iter().any(|(sub, sup)| sub.is_inverse != sup.is_inverse)
+    }
+
+    pub fn is_simple(&self, relation: &Relation) -> bool {
+        // A simple relation is neither transitive nor has transitive subrelations
+        self.transitive_subrelations(relation).is_empty()
+    }
+
+    pub fn check_number_restrictions(&self, concept: &dyn Concept) -> Result<(), NonSimpleRelationError> {
+        // Number restrictions are decidable only on the simple relations
+        match extract_number_restrictions(concept).into_iter().find(|(r, _)| !self.is_simple(r)) {
+            Some((relation, restriction)) => Err(NonSimpleRelationError { relation: relation.to_string(), concept: restriction.to_string() }),
+            None => Ok(())
+        }
+    }
+
+    pub fn transitive_subrelations(&self, relation: &Relation) -> Vec<Relation> {
+        // All the transitive s, such that s ⊑ r (these are needed for the ∀⁺ rule)
+        sorted(self.transitive_relations
+            .iter()
+            .filter(|s| self.is_subrelation(s, relation))
+            .cloned()
+            .collect())
+    }
+}
+
+fn sorted(mut relations: Vec<Relation>) -> Vec<Relation> {
+    // Hash sets have no stable order, but the tableau should expand the concepts in the same order on every run
+    relations.sort_by(|r, s| (&r.name, r.is_inverse).cmp(&(&s.name, s.is_inverse)));
+    relations
+}
+
+impl fmt::Display for RBox {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let inclusions = self.inclusions.iter()
+            .filter(|(sub, _)| !sub.is_inverse)
+            .map(|(sub, sup)| format!("{} -> {}", sub, sup));
+        let transitive_relations = self.transitive_relations.iter()
+            .filter(|r| !r.is_inverse)
+            .map(|r| format!("transitive {}", r));
+
+        let functional_relations = self.functional_relations.iter()
+            .map(|r| match r.is_inverse {
+                true => format!("inverseFunctional {}", r.inverse()),
+                false => format!("functional {}", r)
+            });
+
+        write!(fmt, "RBox:\n  - {}", inclusions.chain(transitive_relations).chain(functional_relations)
+            .collect::<Vec<String>>().join("\n  - "))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hierarchy_closure() {
+        let mut rbox = RBox::new();
+        add_rbox_axiom(&mut rbox, "role hasSon -> hasChild").unwrap();
+        add_rbox_axiom(&mut rbox, "role hasChild -> hasDescendant").unwrap();
+        add_rbox_axiom(&mut rbox, "role hasParent == hasChild-").unwrap();
+
+        assert!(rbox.is_subrelation(&Relation::new("hasSon"), &Relation::new("hasDescendant")));
+        assert!(rbox.is_subrelation(&Relation::new("hasSon-"), &Relation::new("hasDescendant-")));
+        assert!(rbox.is_subrelation(&Relation::new("hasParent"), &Relation::new("hasDescendant-")));
+        assert!(rbox.is_subrelation(&Relation::new("hasSon-"), &Relation::new("hasParent")));
+        assert!(!rbox.is_subrelation(&Relation::new("hasDescendant"), &Relation::new("hasSon")));
+        assert!(add_rbox_axiom(&mut rbox, "role hasSon hasChild").is_err());
+    }
+
+    #[test]
+    fn test_transitive_subrelations() {
+        let mut rbox = RBox::new();
+        add_rbox_axiom(&mut rbox, "role hasChild -> hasDescendant").unwrap();
+        add_rbox_axiom(&mut rbox, "transitive hasDescendant").unwrap();
+
+        assert!(rbox.is_transitive(&Relation::new("hasDescendant-")));
+        assert!(rbox.transitive_subrelations(&Relation::new("hasChild")).is_empty());
+        assert_eq!(rbox.transitive_subrelations(&Relation::new("hasDescendant-")), vec![Relation::new("hasDescendant-")]);
+        assert!(rbox.is_simple(&Relation::new("hasChild")));
+        assert!(!rbox.is_simple(&Relation::new("hasDescendant-")));
+    }
+}
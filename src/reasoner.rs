@@ -1,643 +1,1950 @@
+use std::fmt;
 use std::collections::{HashSet, HashMap};
 use std::iter::FromIterator;
+use std::cell::RefCell;
+use std::mem;
 
 use concept::*;
 use abox::*;
 use tbox::*;
+use rbox::RBox;
+use knowledge_base::KnowledgeBase;
+use taxonomy::Taxonomy;
+use completion_graph::{CompletionGraph, DependencySet, RuleType};
+use concept_factory::{ConceptFactory, ConceptId, ConceptNode};
+use satisfiability_cache::{SatisfiabilityCache, label_key};
+use pseudo_model::PseudoModel;
+use told_subsumers::ToldSubsumers;
+use error::KnowledgeBaseError;
+
+
+/// Reasoning services over a knowledge base.
+/// TBox definitions are turned into lazy unfolding rules and GCIs are aggregated once, on construction,
+/// so that the queries below only have to run the tableau.
+/// The queries, which do not need a model, share a satisfiability cache.
+/// Subsumptions between the concept names, which are obvious from the axioms, do not run the tableau at all.
+#[derive(Debug, Clone)]
+pub struct Reasoner {
+    abox: ABox,
+    tbox: TBox,
+    unfolding_rules: Vec<TBoxAxiom>,
+    super_concept: Option<Box<dyn Concept>>,
+    // The root individual of a concept query should differ from these ones
+    nominal_individuals: HashSet<Individual>,
+    told_subsumers: ToldSubsumers,
+    // How many of the GCIs have become lazy unfolding rules (out of all of them)
+    num_absorbed_inclusions: (usize, usize),
+    strategy: ExpansionStrategy,
+    cache: RefCell<SatisfiabilityCache>,
+    // Built by the first query, which needs it, and shared by the other ones
+    taxonomy: RefCell<Option<Taxonomy>>,
+    // Summed up over all the queries
+    statistics: RefCell<TableauStatistics>
+}
 
+impl Reasoner {
+    pub fn new(kb: KnowledgeBase) -> Result<Reasoner, KnowledgeBaseError> {
+        let KnowledgeBase { abox, tbox, allow_cyclic_definitions } = kb;
 
-pub fn tableau_reasoning(abox: ABox, super_concept: Option<Box<dyn Concept>>) -> Option<ABox> {
-    debug!("\n\n<======== Starting tableau algorithm ========>\n");
-    let mut aboxes = vec![abox];
+        // Cyclic definitions are likely to be a mistake, so they become GCIs only if the knowledge base allows them
+        if !allow_cyclic_definitions {
+            tbox.check_definitions()?;
+        }
 
-    while aboxes.len() > 0 {
-        debug!("Current number of aboxes: {}", aboxes.len());
-        let abox = aboxes.pop().unwrap();
-        debug!("Considering {}", abox);
-        let new_aboxes = perform_tableu_reasoning_step(&abox, &super_concept);
+        // Number restrictions on the relations, which are not simple, would make the tableau incomplete
+        tbox.check_number_restrictions()?;
+        abox.axioms.iter()
+            .filter_map(|a| a.downcast_ref::<ConceptAxiom>())
+            .try_for_each(|a| tbox.rbox.check_number_restrictions(&*a.concept))?;
+
+        let Absorption { unfolding_rules, super_concept, num_absorbed, num_inclusions } = tbox.absorb();
+        let nominal_individuals = tbox.nominal_individuals();
+        let told_subsumers = ToldSubsumers::new(&tbox);
+
+        Ok(Reasoner {
+            abox, tbox, unfolding_rules, super_concept, nominal_individuals, told_subsumers,
+            num_absorbed_inclusions: (num_absorbed, num_inclusions),
+            strategy: ExpansionStrategy::default(),
+            cache: RefCell::new(SatisfiabilityCache::new()),
+            taxonomy: RefCell::new(None),
+            statistics: RefCell::new(TableauStatistics::default())
+        })
+    }
 
-        if new_aboxes.is_empty() {
-            // Hooray! We have terminated! This means, that we have reached a consistent leave
-            return Some(abox);
+    pub fn with_unique_name_assumption(mut self, unique_name_assumption: bool) -> Reasoner {
+        // Makes all the named individuals (including the ones from nominals) pairwise different
+        self.abox.unique_name_assumption = unique_name_assumption;
+        self
+    }
+
+    pub fn with_strategy(mut self, strategy: ExpansionStrategy) -> Reasoner {
+        self.strategy = strategy;
+        self
+    }
+
+    pub fn num_absorbed_inclusions(&self) -> (usize, usize) {
+        self.num_absorbed_inclusions
+    }
+
+    pub fn find_model(&self) -> Option<Model> {
+        self.run_tableau(self.abox.clone()).map(|a| a.extract_model())
+    }
+
+    pub fn is_consistent(&self) -> bool {
+        self.check_tableau(self.abox.clone(), false)
+    }
+
+    pub fn find_concept_model(&self, concept: &dyn Concept) -> Option<Model> {
+        self.run_tableau(self.concept_abox(concept)).map(|a| a.extract_model())
+    }
+
+    pub fn is_satisfiable(&self, concept: &dyn Concept) -> bool {
+        self.check_tableau(self.concept_abox(concept), true)
+    }
+
+    pub fn find_subsumption_counter_model(&self, sub: &dyn Concept, sup: &dyn Concept) -> Option<Model> {
+        // C ⊑ D holds iff C ⊓ ¬D is unsatisfiable
+        if self.is_told_subsumed(sub, sup) {
+            debug!("{} is a told subsumer of {}", sup, sub);
+            return None;
         }
 
-        let non_clashed_aboxes: Vec<ABox> = new_aboxes
-            .into_iter()
-            .filter(|a| a.is_consistent != Some(false))
-            .collect();
+        // If C ⊑ ¬D is told, every model of C is a model of C ⊓ ¬D
+        if self.is_told_disjoint(sub, sup) {
+            debug!("{} and {} are told to be disjoint", sub, sup);
+            return self.find_concept_model(sub);
+        }
 
-        aboxes.extend(non_clashed_aboxes);
+        // Mergeable pseudo-models only tell that C ⊓ ¬D is satisfiable, but its model is built by the tableau anyway
+        self.find_concept_model(&ConjunctionConcept {
+            subconcepts: vec![sub.clone_box(), sup.negate()]
+        })
     }
 
-    None
-}
+    pub fn is_subsumed(&self, sub: &dyn Concept, sup: &dyn Concept) -> bool {
+        if self.is_told_subsumed(sub, sup) {
+            debug!("{} is a told subsumer of {}", sup, sub);
+            return true;
+        }
 
-fn perform_tableu_reasoning_step(abox: &ABox, super_concept: &Option<Box<dyn Concept>>) -> Vec<ABox> {
-    // 1. Trying "and-rule
-    let new_abox =  apply_conjunction_rule(abox);
-    if new_abox.is_some() { return vec![new_abox.unwrap()]; }
+        // If C ⊑ ¬D is told, C ⊓ ¬D is just C (and its satisfiability is checked once for all such D's)
+        if self.is_told_disjoint(sub, sup) {
+            debug!("{} and {} are told to be disjoint", sub, sup);
+            return !self.is_satisfiable(sub);
+        }
 
-    // 2. Trying "or"-rule
-    let new_aboxes = apply_disjunction_rule(abox);
-    if new_aboxes.len() > 0 { return new_aboxes; }
+        // Most of the subsumptions do not hold, and if the models of C and ¬D can be merged, C ⊓ ¬D is satisfiable
+        let sup_negation = sup.negate();
 
-    // 3. Trying "only"-rule
-    let new_abox =  apply_only_rule(abox);
-    if new_abox.is_some() { return vec![new_abox.unwrap()]; }
+        if self.can_merge_models(sub, &*sup_negation) {
+            debug!("The models of {} and {} can be merged, so there is no subsumption", sub, sup_negation);
+            return false;
+        }
 
-    // 4. Trying "<="-rule
-    let new_aboxes =  apply_at_most_rule(abox);
-    if new_aboxes.len() > 0 { return new_aboxes; }
+        !self.is_satisfiable(&ConjunctionConcept {
+            subconcepts: vec![sub.clone_box(), sup_negation]
+        })
+    }
 
-    // 5. Trying "choose"-rule
-    let new_aboxes =  apply_choose_rule(abox);
-    if new_aboxes.len() > 0 { return new_aboxes; }
+    fn is_told_subsumed(&self, sub: &dyn Concept, sup: &dyn Concept) -> bool {
+        match (concept_name(sub), concept_name(sup)) {
+            (Some(sub_name), Some(sup_name)) => self.told_subsumers.is_subsumed(sub_name, sup_name),
+            _ => false
+        }
+    }
 
-    // 6. Trying "GCI"-rule
-    let new_abox = apply_gci_rule(abox, super_concept);
-    if new_abox.is_some() { return vec![new_abox.unwrap()]; }
+    fn is_told_disjoint(&self, sub: &dyn Concept, sup: &dyn Concept) -> bool {
+        match (concept_name(sub), concept_name(sup)) {
+            (Some(sub_name), Some(sup_name)) => self.told_subsumers.are_disjoint(sub_name, sup_name),
+            _ => false
+        }
+    }
 
-    // 7. Trying "some"-rule (lower prioritity since it is generative)
-    let new_abox =  apply_some_rule(abox);
-    if new_abox.is_some() { return vec![new_abox.unwrap()]; }
+    fn can_merge_models(&self, lhs: &dyn Concept, rhs: &dyn Concept) -> bool {
+        // Only the pseudo-models, which are already in the cache, are merged (e.g. the ones from the classification):
+        // otherwise building them would take more tableau runs than the check of C ⊓ ¬D itself
+        let (lhs_label, rhs_label) = match (self.concept_label(lhs), self.concept_label(rhs)) {
+            (Some(lhs_label), Some(rhs_label)) => (lhs_label, rhs_label),
+            _ => return false
+        };
+        let cache = self.cache.borrow();
 
-    // 8. Trying "at-least"-rule (lower prioritity since it is generative)
-    let new_abox =  apply_at_least_rule(abox);
-    if new_abox.is_some() { return vec![new_abox.unwrap()]; }
+        match (cache.pseudo_model(&lhs_label), cache.pseudo_model(&rhs_label)) {
+            (Some(lhs_model), Some(rhs_model)) => lhs_model.can_merge(rhs_model, &self.tbox.rbox),
+            _ => false
+        }
+    }
 
-    vec![]
-}
+    fn concept_label(&self, concept: &dyn Concept) -> Option<Vec<ConceptId>> {
+        // Satisfiability checks keep the pseudo-models of the concepts in the cache under their labels
+        find_concept_label(&self.concept_abox(concept), &mut self.cache.borrow_mut().factory)
+    }
 
+    #[cfg(test)]
+    fn find_pseudo_model(&self, concept: &dyn Concept) -> Option<PseudoModel> {
+        self.is_satisfiable(concept);
+        self.cached_pseudo_model(concept)
+    }
 
-fn apply_conjunction_rule(abox: &ABox) -> Option<ABox> {
-    /// This function applies all conjunction axioms it finds at the current level
-    /// (since they do not create any additional nodes, we can apply them all at once)
-    let conjunction_axioms = extract_concept_axioms(abox, ConceptType::Conjunction);
+    #[cfg(test)]
+    fn cached_pseudo_model(&self, concept: &dyn Concept) -> Option<PseudoModel> {
+        let label = self.concept_label(concept)?;
+        self.cache.borrow().pseudo_model(&label).cloned()
+    }
 
-    if conjunction_axioms.is_empty() {
-        debug!("Tried to expand AND rule, but there are no relevant axioms.");
-        return None; // Cannot apply and-rule
+    fn concept_abox(&self, concept: &dyn Concept) -> ABox {
+        // Concept satisfiability is checked w.r.t. the TBox only,
+        // i.e. the ABox of the knowledge base is not taken into account
+        let x = self.root_individual(concept);
+        let mut abox = ABox::new();
+
+        abox.unique_name_assumption = self.abox.unique_name_assumption;
+        abox.add_individual(x.clone());
+        abox.axioms.insert(Box::new(ConceptAxiom {
+            concept: concept.convert_to_nnf(),
+            individual: x
+        }) as Box<dyn ABoxAxiom>);
+
+        abox
     }
 
-    let new_axioms = conjunction_axioms
-        .iter()
-        .map(|a| {
-            let concept = a.concept.downcast_ref::<ConjunctionConcept>().unwrap();
-            create_new_axioms(concept.subconcepts.clone(), a.individual.clone(), abox)
-        })
-        .find(|new_axioms| !new_axioms.is_empty());
+    fn root_individual(&self, concept: &dyn Concept) -> Individual {
+        // The root is usually named "a", but a nominal {a} would refer to it then,
+        // so it gets the first name of "a", "a1", "a2", ..., which no nominal refers to
+        let concept_individuals = extract_nominal_individuals(concept);
 
-    if new_axioms.is_none() {
-        debug!("Tried to expand AND rule, but the expansion is already in ABox.");
-        return None; // We have not found any expandable and-rule
+        (0..)
+            .map(|i| Individual { name: if i == 0 { "a".to_string() } else { format!("a{}", i) } })
+            .find(|x| !self.nominal_individuals.contains(x) && !concept_individuals.contains(x))
+            .unwrap()
     }
 
-    let new_axioms = new_axioms.unwrap();
-    let mut new_abox = abox.clone();
-    let num_applicable_axioms = new_axioms
-        .iter()
-        .map(|a| a.downcast_ref::<ConceptAxiom>().unwrap())
-        .map(|a| ConceptAxiom { concept: a.concept.negate(), individual: a.individual.clone() }) // Negating
-        .map(|a| Box::new(a) as Box<dyn ABoxAxiom>)
-        .filter(|a| !new_abox.axioms.contains(&*a))
-        .count();
+    pub fn classify(&self) -> Taxonomy {
+        // Told subsumers go first, so most of the concepts are inserted below the ones, which are already there
+        self.taxonomy
+            .borrow_mut()
+            .get_or_insert_with(|| Taxonomy::build(self, &self.told_subsumers.sort_by_subsumers(&self.tbox.concept_names()),
+                                                   &self.tbox.names_without_subsumees()))
+            .clone()
+    }
 
-    if num_applicable_axioms != new_axioms.len() {
-        // Conjunction rule was applied and we got an incosistent abox
-        new_abox.is_consistent = Some(false);
-        debug!("Obtained an incosistency while expanding AND rule.");
+    pub fn told_subsumers(&self) -> &ToldSubsumers {
+        &self.told_subsumers
     }
 
-    new_abox.axioms.extend(new_axioms);
+    pub fn is_instance(&self, x: &Individual, concept: &dyn Concept) -> bool {
+        // x is an instance of C iff the ABox becomes inconsistent after adding ¬C(x)
+        let concept = concept.convert_to_nnf();
+        let mut abox = self.abox.clone();
 
-    debug!("Successfully expanded AND rule.");
-    Some(new_abox)
-}
+        if abox.axioms.contains(&(Box::new(ConceptAxiom { concept: concept.clone(), individual: x.clone() }) as Box<dyn ABoxAxiom>)) {
+            return true; // No need to run the tableau for the told instances
+        }
+
+        abox.axioms.insert(Box::new(ConceptAxiom {
+            concept: concept.negate().convert_to_nnf(),
+            individual: x.clone()
+        }) as Box<dyn ABoxAxiom>);
 
+        !self.check_tableau(abox, false)
+    }
 
-fn apply_disjunction_rule(abox: &ABox) -> Vec<ABox> {
-    /// This function expands a single disjunction rule among all the disjunction rules
-    /// it finds at the current level. It expands the first one expandable.
-    let disjunction_axioms = extract_concept_axioms(abox, ConceptType::Disjunction);
+    fn run_tableau(&self, abox: ABox) -> Option<ABox> {
+        let (result, statistics) = tableau_reasoning_with_statistics(abox, self.super_concept.clone(), &self.unfolding_rules,
+                                                                     &self.tbox.rbox, self.strategy);
+        debug!("Tableau statistics: {:?}", statistics);
+        self.statistics.borrow_mut().add(&statistics);
 
-    if disjunction_axioms.is_empty() {
-        debug!("Tried to expand OR rule, but there are no relevant axioms.");
-        return vec![]; // Cannot apply the rule
+        result
     }
 
-    for axiom in disjunction_axioms {
-        let concept = axiom.concept.downcast_ref::<DisjunctionConcept>().unwrap();
-        let new_axioms = create_new_axioms(concept.subconcepts.clone(), axiom.individual.clone(), abox);
+    fn check_tableau(&self, abox: ABox, is_concept_query: bool) -> bool {
+        let mut cache = self.cache.borrow_mut();
+        let (is_consistent, statistics) = tableau_consistency(abox, self.super_concept.clone(), &self.unfolding_rules,
+                                                              &self.tbox.rbox, self.strategy, is_concept_query, &mut cache);
+        debug!("Tableau statistics: {:?}", statistics);
+        self.statistics.borrow_mut().add(&statistics);
 
-        if new_axioms.len() < concept.subconcepts.len() {
-            // Some of the axioms are already in the abox which means
-            // that we cannot expand with or-rule here.
-            continue;
+        is_consistent
+    }
+
+    pub fn statistics(&self) -> TableauStatistics {
+        // What the tableau has done for all the queries so far
+        self.statistics.borrow().clone()
+    }
+
+    pub fn instances(&self, concept: &dyn Concept) -> Vec<Individual> {
+        // The told types of an individual (the asserted names and negated names) decide most of the queries
+        // without a tableau run for it: if a type is subsumed by C, the individual is an instance of C,
+        // and if it is disjoint with C, it is not. A type is tested once for all the individuals,
+        // and the taxonomy answers the subsumptions between the names
+        let mut individuals = self.abox.individuals.iter().cloned().collect::<Vec<Individual>>();
+        individuals.sort_by(|x, y| x.name.cmp(&y.name));
+
+        // Every individual is an instance of everything in an inconsistent ABox
+        if !self.is_consistent() {
+            return individuals;
         }
 
-        // Ok, good. We can now expand with the or-rule!
-        debug!("Successfully expanded OR rule.");
-        return new_axioms
+        let taxonomy = self.classify();
+        let told_types = self.told_types();
+        let mut decisions = HashMap::new();
+
+        individuals
             .into_iter()
-            .map(|a| create_new_abox_from_concept_axiom(a, abox))
-            .collect::<Vec<ABox>>();
+            .filter(|x| {
+                let decision = told_types.get(x).into_iter().flatten().find_map(|t| {
+                    *decisions.entry(t.to_string()).or_insert_with(|| self.decide_instance(&taxonomy, &**t, concept))
+                });
+
+                decision.unwrap_or_else(|| self.is_instance(x, concept))
+            })
+            .collect()
     }
 
-    debug!("All OR axioms are non-expandable.");
+    fn told_types(&self) -> HashMap<Individual, Vec<Box<dyn Concept>>> {
+        // The asserted concept names and negated concept names of the individuals, sorted to make the tests deterministic
+        let mut told_types: HashMap<Individual, Vec<Box<dyn Concept>>> = HashMap::new();
 
-    vec![]
-}
+        for axiom in self.abox.axioms.iter().filter_map(|a| a.downcast_ref::<ConceptAxiom>()) {
+            let is_literal = match axiom.concept.downcast_ref::<NotConcept>() {
+                Some(negation) => negation.subconcept.downcast_ref::<AtomicConcept>().is_some(),
+                None => axiom.concept.downcast_ref::<AtomicConcept>().is_some()
+            };
 
+            if is_literal {
+                told_types.entry(axiom.individual.clone()).or_default().push(axiom.concept.clone());
+            }
+        }
 
-fn apply_only_rule(abox: &ABox) -> Option<ABox> {
-    let only_axioms = extract_concept_axioms(abox, ConceptType::Only);
+        for types in told_types.values_mut() {
+            types.sort_by_key(|t| t.to_string());
+        }
 
-    if only_axioms.is_empty() {
-        debug!("Tried to expand ONLY rule, but there are no relevant axioms.");
-        return None;
+        told_types
     }
 
-    for axiom in only_axioms {
-        let concept = axiom.concept.downcast_ref::<OnlyConcept>().unwrap();
-        let other_individuals = extract_rhs_for_relation(&concept.relation, &axiom.individual, abox);
-        let new_axiom = other_individuals
-            .into_iter()
-            .map(|y| Box::new(ConceptAxiom {
-                concept: concept.subconcept.clone() as Box<dyn Concept>,
-                individual: y
-            }) as Box::<dyn ABoxAxiom>)
-            .find(|a| !abox.axioms.contains(a));
+    fn decide_instance(&self, taxonomy: &Taxonomy, told_type: &dyn Concept, concept: &dyn Concept) -> Option<bool> {
+        // Some(true) if every instance of the told type is an instance of the concept, Some(false) if none of them is
+        let is_subsumed = match (concept_name(told_type), concept_name(concept)) {
+            (Some(sub), Some(sup)) => taxonomy.is_subsumed(sub, sup).unwrap_or_else(|| self.is_subsumed(told_type, concept)),
+            _ => self.is_subsumed(told_type, concept)
+        };
 
-        if new_axiom.is_none() {
-            continue;
+        if is_subsumed {
+            return Some(true);
         }
 
-        debug!("Successfully expanded ONLY rule: {} => {}", axiom, new_axiom.clone().unwrap());
-        return Some(create_new_abox_from_concept_axiom(new_axiom.unwrap(), abox))
+        let is_disjoint = !self.is_satisfiable(&ConjunctionConcept {
+            subconcepts: vec![told_type.clone_box(), concept.clone_box()]
+        });
+
+        if is_disjoint { Some(false) } else { None }
     }
 
-    debug!("All ONLY axioms are non-expandable.");
+    pub fn realize(&self) -> Vec<(Individual, Vec<String>)> {
+        // Finds the most specific named concepts for every individual of the ABox
+        let taxonomy = self.classify();
+        let mut individuals = self.abox.individuals.iter().cloned().collect::<Vec<Individual>>();
+        individuals.sort_by(|x, y| x.name.cmp(&y.name));
 
-    None
+        individuals
+            .into_iter()
+            .map(|x| {
+                let types = taxonomy.most_specific_concepts(|name| self.is_instance(&x, &AtomicConcept { name: name.to_string() }));
+                (x, types)
+            })
+            .collect()
+    }
 }
 
 
-fn apply_some_rule(abox: &ABox) -> Option<ABox> {
-    let some_axioms = extract_concept_axioms(abox, ConceptType::Some);
+fn concept_name(concept: &dyn Concept) -> Option<&str> {
+    concept.downcast_ref::<AtomicConcept>().map(|c| c.name.as_str())
+}
 
-    if some_axioms.is_empty() {
-        debug!("Tried to expand SOME rule, but there are no relevant axioms.");
-        return None;
-    }
 
-    for axiom in some_axioms {
-        if let Some(x) = find_blocker(abox, &axiom.individual) {
-            debug!("Tried to expand {}, but it is blocked by {}.", axiom, x);
-            continue;
-        }
+/// A nondeterministic choice of the tableau: the position on the trail, where the choice was made,
+/// the alternatives, which are not tried yet, and the branch points, which the clashes
+/// in the tried ones depend on (except for this one)
+struct BranchPoint {
+    mark: usize,
+    alternatives: Vec<Choice>,
+    clash_set: DependencySet
+}
 
-        let concept = axiom.concept.downcast_ref::<SomeConcept>().unwrap();
-        let rhs_individuals = extract_rhs_for_relation(&concept.relation, &axiom.individual, abox);
-        let rhs_concept_axiom = rhs_individuals
-            .into_iter()
-            .map(|y| Box::new(ConceptAxiom {
-                concept: concept.subconcept.clone() as Box<dyn Concept>,
-                individual: y
-            }) as Box::<dyn ABoxAxiom>)
-            .find(|a| abox.axioms.contains(a));
+/// An alternative of a nondeterministic rule
+#[derive(Debug)]
+enum Choice {
+    AddConcepts(Individual, Vec<ConceptId>, DependencySet),
+    Merge(Individual, Individual, DependencySet),
+    Clash(DependencySet)
+}
 
-        if rhs_concept_axiom.is_some() {
-            continue;
+/// Lazy unfolding rules over the concept ids: the concepts, which are added along with the lhs
+type UnfoldingRules = HashMap<ConceptId, Vec<ConceptId>>;
+
+enum Expansion {
+    Complete,
+    Deterministic,
+    Nondeterministic(Vec<Choice>)
+}
+
+/// Which rule the tableau applies, when several of them are applicable.
+/// By default the rules are tried in this order: "and", unfolding, nominal, "or", "only", "<=", choose, GCI, "some", "at-least",
+/// and the oldest disjunction is expanded first
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExpansionStrategy {
+    // The deterministic rules ("and", unfolding, "only" and GCI) are applied until none of them is applicable,
+    // and only then the other ones are tried
+    pub deterministic_first: bool,
+    // The disjunction with the fewest disjuncts left is expanded first, i.e. the one with the smallest branching factor
+    pub smallest_disjunction_first: bool
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Rule { Conjunction, Unfolding, Nominal, Disjunction, Only, AtMost, Choose, Gci, Some, AtLeast }
+
+impl ExpansionStrategy {
+    fn rule_order(&self) -> &'static [Rule] {
+        // The generating rules ("some" and "at-least") go last anyway, so that the labels are complete before the blocking checks
+        match self.deterministic_first {
+            false => &[Rule::Conjunction, Rule::Unfolding, Rule::Nominal, Rule::Disjunction, Rule::Only,
+                       Rule::AtMost, Rule::Choose, Rule::Gci, Rule::Some, Rule::AtLeast],
+            true => &[Rule::Conjunction, Rule::Unfolding, Rule::Only, Rule::Gci, Rule::Nominal,
+                      Rule::Disjunction, Rule::AtMost, Rule::Choose, Rule::Some, Rule::AtLeast]
         }
+    }
+}
 
-        let new_individual = Individual { name: format!("x_#{}", abox.individuals.len()) };
-        debug!("Creating new individual: {}", new_individual.name);
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TableauStatistics {
+    // Queries, which have run the tableau (instead of being answered from the cache)
+    pub num_runs: usize,
+    pub num_steps: usize,
+    pub num_branch_points: usize,
+    pub num_backjumps: usize,
+    // Disjunctions, which had a single disjunct left, i.e. were expanded deterministically
+    pub num_propagations: usize,
+    // Disjuncts, which were not tried, since their negations were already in the label
+    pub num_saved_branches: usize,
+    // Labels (of the fresh successors or of the whole concept), which were found in the satisfiability cache
+    pub num_cache_hits: usize
+}
 
-        let new_axiom = Box::new(ConceptAxiom {
-            concept: concept.subconcept.clone() as Box<dyn Concept>,
-            individual: new_individual.clone()
-        }) as Box<dyn ABoxAxiom>;
-
-        let mut new_abox = create_new_abox_from_concept_axiom(new_axiom, abox);
-        new_abox.add_individual(new_individual.clone());
-        new_abox.axioms.insert(Box::new(RelationAxiom {
-            lhs: axiom.individual.clone(),
-            rhs: new_individual.clone(),
-            relation: concept.relation.clone()
-        }) as Box<dyn ABoxAxiom>);
+impl TableauStatistics {
+    fn add(&mut self, other: &TableauStatistics) {
+        self.num_runs += other.num_runs;
+        self.num_steps += other.num_steps;
+        self.num_branch_points += other.num_branch_points;
+        self.num_backjumps += other.num_backjumps;
+        self.num_propagations += other.num_propagations;
+        self.num_saved_branches += other.num_saved_branches;
+        self.num_cache_hits += other.num_cache_hits;
+    }
+}
 
-        debug!("Successfully expanded SOME rule: {}", axiom);
-        return Some(new_abox);
+impl fmt::Display for TableauStatistics {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{} tableau runs, {} steps, {} branch points, {} backjumps, {} propagated disjunctions, {} saved branches, {} cache hits",
+               self.num_runs, self.num_steps, self.num_branch_points, self.num_backjumps,
+               self.num_propagations, self.num_saved_branches, self.num_cache_hits)
     }
+}
+
 
-    debug!("All SOME axioms are non-expandable.");
+pub fn tableau_reasoning(abox: ABox, super_concept: Option<Box<dyn Concept>>,
+                         unfolding_rules: &[TBoxAxiom], rbox: &RBox, strategy: ExpansionStrategy) -> Option<ABox> {
+    let (result, statistics) = tableau_reasoning_with_statistics(abox, super_concept, unfolding_rules, rbox, strategy);
+    debug!("Tableau statistics: {:?}", statistics);
 
-    None
+    result
 }
 
+pub fn tableau_reasoning_with_statistics(abox: ABox, super_concept: Option<Box<dyn Concept>>, unfolding_rules: &[TBoxAxiom],
+                                         rbox: &RBox, strategy: ExpansionStrategy) -> (Option<ABox>, TableauStatistics) {
+    let mut statistics = TableauStatistics { num_runs: 1, ..Default::default() };
+    let mut graph = CompletionGraph::from_abox(&abox);
+    let super_concept = super_concept.map(|c| graph.factory.intern(&*c));
+    let unfolding_rules = intern_unfolding_rules(&mut graph, unfolding_rules);
+
+    // The cache is empty, since all the successors of a model should be expanded
+    match expand_graph(&mut graph, super_concept, &unfolding_rules, rbox, strategy, &mut SatisfiabilityCache::new(), &mut statistics) {
+        true => (Some(graph.to_abox()), statistics),
+        false => (None, statistics)
+    }
+}
+
+/// Checks the consistency of the ABox like `tableau_reasoning` does, but reuses the labels from the cache
+/// and adds the new ones to it. No model is built, since the successors with satisfiable labels are not expanded.
+/// The label of the root is cached only for a concept query: an individual of a knowledge base is not a fresh one.
+/// The nominals of the TBox could refer to any individual, so with them nothing is cached at all.
+pub fn tableau_consistency(abox: ABox, super_concept: Option<Box<dyn Concept>>, unfolding_rules: &[TBoxAxiom], rbox: &RBox,
+                           strategy: ExpansionStrategy, is_concept_query: bool, cache: &mut SatisfiabilityCache) -> (bool, TableauStatistics) {
+    let mut statistics = TableauStatistics::default();
+    let is_cacheable = !has_nominal_axioms(&super_concept, unfolding_rules);
+    let concept_label = match is_concept_query && is_cacheable {
+        true => find_concept_label(&abox, &mut cache.factory),
+        false => None
+    };
+
+    if let Some(is_satisfiable) = concept_label.as_ref().and_then(|label| cache.is_concept_satisfiable(label)) {
+        statistics.num_cache_hits += 1;
+        return (is_satisfiable, statistics);
+    }
 
-fn apply_at_least_rule(abox: &ABox) -> Option<ABox> {
-    let at_least_axioms = extract_concept_axioms(abox, ConceptType::AtLeast);
+    statistics.num_runs += 1;
 
-    if at_least_axioms.is_empty() {
-        debug!("Tried to expand AtLeast rule, but there are no relevant axioms.");
-        return None;
+    let mut graph = CompletionGraph::from_abox_with_factory(&abox, mem::take(&mut cache.factory));
+    let super_concept = super_concept.map(|c| graph.factory.intern(&*c));
+    let unfolding_rules = intern_unfolding_rules(&mut graph, unfolding_rules);
+    let concepts = find_used_concepts(&graph, super_concept, &unfolding_rules);
+
+    cache.reuse_satisfiable = allows_cached_successors(&graph.factory, &concepts, rbox, cache);
+    let is_consistent = expand_graph(&mut graph, super_concept, &unfolding_rules, rbox, strategy, cache, &mut statistics);
+
+    if is_consistent && is_cacheable {
+        cache_satisfiable_successors(&mut graph, rbox, cache);
     }
 
-    let expandable_axiom = at_least_axioms
-        .iter()
-        .find(|a| {
-            let concept = a.concept.downcast_ref::<AtLeastConcept>().unwrap();
+    match concept_label {
+        Some(label) if is_consistent => {
+            // The label of the root is complete, even if its successors are cached
+            let model = match allows_model_merging(&graph.factory, &concepts, rbox, cache) {
+                true => Some(PseudoModel::new(&graph.factory, &graph.label(&graph.individuals()[0]), rbox)),
+                false => None
+            };
+            cache.insert_model(label, model);
+        },
+        Some(label) => cache.insert(label, false),
+        None => {}
+    }
+
+    cache.factory = mem::take(&mut graph.factory);
+    (is_consistent, statistics)
+}
+
+/// Runs the tableau with dependency-directed backtracking (backjumping):
+/// every fact knows the branch points it depends on, so on a clash we jump back
+/// directly to the most recent branch point, which is responsible for it.
+fn expand_graph(graph: &mut CompletionGraph, super_concept: Option<ConceptId>, unfolding_rules: &UnfoldingRules, rbox: &RBox,
+                strategy: ExpansionStrategy, cache: &mut SatisfiabilityCache, statistics: &mut TableauStatistics) -> bool {
+    debug!("\n\n<======== Starting tableau algorithm ========>\n");
+
+    let mut branch_points: Vec<BranchPoint> = vec![];
 
-            if let Some(x) = find_blocker(abox, &a.individual) {
-                debug!("Tried to expand {}, but it is blocked by {}.", a, x);
+    if let Some(dependencies) = find_negative_relation_clash(graph, rbox) {
+        graph.set_clash(&dependencies);
+    }
+
+    loop {
+        while let Some(clash_set) = graph.clash().cloned() {
+            debug!("Obtained a clash, which depends on the branch points {:?}", clash_set);
+
+            if !backjump(graph, &mut branch_points, clash_set, statistics, rbox) {
                 return false;
             }
+        }
 
-            let possible_rhs: HashSet<Individual> = HashSet::from_iter(
-                extract_rhs_for_relation(&concept.relation, &a.individual, abox).iter().cloned());
+        debug!("Current number of branch points: {}", branch_points.len());
+        debug!("Considering {}", graph);
+        statistics.num_steps += 1;
 
-            // Searching for a set of pairwise different individuals that would satisfy the constraints
-            abox.pairwise_different_individuals.iter().find(|&diff_individuals| {
-                if diff_individuals.len() < concept.amount {
-                    return false;
+        match perform_tableu_reasoning_step(graph, super_concept, unfolding_rules, rbox, strategy, cache, statistics) {
+            // Hooray! We have terminated! This means, that we have reached a consistent leave
+            Expansion::Complete => return true,
+            Expansion::Deterministic => {},
+            Expansion::Nondeterministic(mut choices) => {
+                if choices.len() == 1 {
+                    apply_choice(graph, choices.pop().unwrap(), None, rbox);
+                    continue;
                 }
 
-                diff_individuals.iter().all(|rhs| {
-                    let contains_relation = possible_rhs.contains(rhs);
-                    let contains_concept = abox.axioms.contains(&(Box::new(ConceptAxiom {
-                        individual: a.individual.clone(),
-                        concept: concept.subconcept.clone()
-                    }) as Box::<dyn ABoxAxiom>));
+                // The alternatives are tried from the last one (as they are popped from the branch point).
+                // Everything, which a choice adds, depends on its branch point
+                let first_choice = choices.pop().unwrap();
 
-                    contains_relation && contains_concept
-                })
-            }).is_none() // I.e. "there are no such c_1, ..., c_n, that ..."
+                branch_points.push(BranchPoint { mark: graph.mark(), alternatives: choices, clash_set: DependencySet::new() });
+                statistics.num_branch_points += 1;
+                apply_choice(graph, first_choice, Some(branch_points.len() - 1), rbox);
+            }
+        }
+    }
+}
+
+/// Takes the next alternative of the most recent branch point in the clash set.
+/// The branch points above it are dropped: the clash does not depend on them, so other choices there won't help.
+/// If all the alternatives of a branch point have clashed, we go on with their joint clash set.
+fn backjump(graph: &mut CompletionGraph, branch_points: &mut Vec<BranchPoint>, mut clash_set: DependencySet,
+            statistics: &mut TableauStatistics, rbox: &RBox) -> bool {
+    loop {
+        let id = match clash_set.iter().next_back() {
+            Some(&id) => id,
+            None => return false // The clash does not depend on any choice
+        };
+
+        if branch_points.len() > id + 1 {
+            debug!("Jumping back over {} branch points", branch_points.len() - id - 1);
+            statistics.num_backjumps += 1;
+            branch_points.truncate(id + 1);
+        }
+
+        clash_set.remove(&id);
+        let branch_point = branch_points.last_mut().unwrap();
+        branch_point.clash_set.extend(clash_set);
+        graph.undo(branch_point.mark);
+
+        if let Some(choice) = branch_point.alternatives.pop() {
+            apply_choice(graph, choice, Some(id), rbox);
+            return true;
+        }
+
+        clash_set = branch_points.pop().unwrap().clash_set;
+    }
+}
+
+fn apply_choice(graph: &mut CompletionGraph, choice: Choice, branch_point: Option<usize>, rbox: &RBox) {
+    let with_branch_point = |dependencies: DependencySet| dependencies.into_iter().chain(branch_point).collect::<DependencySet>();
+
+    match choice {
+        Choice::AddConcepts(x, concepts, dependencies) => {
+            let dependencies = with_branch_point(dependencies);
+
+            for concept in concepts {
+                graph.add_concept(&x, concept, &dependencies);
+            }
+        },
+        Choice::Merge(x_old, x_new, dependencies) => merge_individuals(graph, &x_old, &x_new, &with_branch_point(dependencies), rbox),
+        Choice::Clash(dependencies) => graph.set_clash(&with_branch_point(dependencies))
+    }
+}
+
+fn perform_tableu_reasoning_step(graph: &mut CompletionGraph, super_concept: Option<ConceptId>, unfolding_rules: &UnfoldingRules, rbox: &RBox,
+                                 strategy: ExpansionStrategy, cache: &mut SatisfiabilityCache, statistics: &mut TableauStatistics) -> Expansion {
+    // The first applicable rule of the strategy is applied
+    for &rule in strategy.rule_order() {
+        let expansion = match rule {
+            Rule::Conjunction => deterministic(apply_conjunction_rule(graph)),
+            Rule::Unfolding => deterministic(apply_unfolding_rule(graph, unfolding_rules)),
+            // Merges individuals, so the earlier the better
+            Rule::Nominal => nondeterministic(apply_nominal_rule(graph)),
+            Rule::Disjunction => nondeterministic(apply_disjunction_rule(graph, strategy, statistics)),
+            Rule::Only => deterministic(apply_only_rule(graph, rbox)),
+            Rule::AtMost => nondeterministic(apply_at_most_rule(graph, rbox)),
+            Rule::Choose => nondeterministic(apply_choose_rule(graph, rbox)),
+            Rule::Gci => deterministic(apply_gci_rule(graph, super_concept)),
+            // Lower priority since these are generative
+            Rule::Some => deterministic(apply_some_rule(graph, rbox, cache, statistics)),
+            Rule::AtLeast => deterministic(apply_at_least_rule(graph, rbox, cache, statistics))
+        };
+
+        if let Some(expansion) = expansion {
+            return expansion;
+        }
+    }
+
+    Expansion::Complete
+}
+
+fn deterministic(is_applied: bool) -> Option<Expansion> {
+    if is_applied { Some(Expansion::Deterministic) } else { None }
+}
+
+fn nondeterministic(choices: Vec<Choice>) -> Option<Expansion> {
+    if choices.is_empty() { None } else { Some(Expansion::Nondeterministic(choices)) }
+}
+
+
+/// This function expands the first conjunction, which has a conjunct missing in the label
+/// (the conjuncts may clash with each other as well as with the label, but the graph checks it on its own)
+fn apply_conjunction_rule(graph: &mut CompletionGraph) -> bool {
+    graph.skip_done(RuleType::Conjunction, |graph, x, c| match graph.factory.node(c) {
+        ConceptNode::And(cs) => cs.iter().all(|&sc| graph.has_concept(x, sc)),
+        _ => true
+    });
+
+    let expansion = graph.find_concepts(RuleType::Conjunction, |node| match node { ConceptNode::And(cs) => Some(cs.clone()), _ => None })
+        .into_iter()
+        .find_map(|(x, c, subconcepts)| {
+            let new_concepts = subconcepts.into_iter()
+                .filter(|&sc| !graph.has_concept(x, sc))
+                .collect::<Vec<ConceptId>>();
+
+            match new_concepts.is_empty() {
+                true => None,
+                false => Some((x.clone(), new_concepts, graph.concept_dependencies(x, c)))
+            }
         });
 
-    if expandable_axiom.is_none() {
-        debug!("Tried to expand AtLeast rule, but possible expansions are already in ABox.");
-        return None;
+    if expansion.is_none() {
+        debug!("Tried to expand AND rule, but there are no expandable axioms.");
+        return false; // We have not found any expandable and-rule
     }
 
-    let mut new_abox = abox.clone();
-    let axiom = expandable_axiom.unwrap();
-    let concept = axiom.concept.downcast_ref::<AtLeastConcept>().unwrap();
-    let mut new_individuals = HashSet::new();
+    let (x, new_concepts, dependencies) = expansion.unwrap();
 
-    for _ in 0..concept.amount {
-        let new_individual = Individual { name: format!("x_#{}", new_abox.individuals.len()) };
-        debug!("Creating new individual: {}", new_individual.name);
+    for concept in new_concepts {
+        graph.add_concept(&x, concept, &dependencies);
+    }
 
-        // Adding the concept
-        new_abox.axioms.insert(Box::new(ConceptAxiom {
-            concept: concept.subconcept.clone() as Box<dyn Concept>,
-            individual: new_individual.clone()
-        }) as Box<dyn ABoxAxiom>);
+    debug!("Successfully expanded AND rule.");
+    true
+}
 
-        // Adding the relation
-        new_abox.axioms.insert(Box::new(RelationAxiom {
-            relation: concept.relation.clone(),
-            lhs: axiom.individual.clone(),
-            rhs: new_individual.clone()
-        }) as Box<dyn ABoxAxiom>);
 
-        new_individuals.insert(new_individual.clone());
-        new_abox.add_individual(new_individual.clone());
+fn intern_unfolding_rules(graph: &mut CompletionGraph, unfolding_rules: &[TBoxAxiom]) -> UnfoldingRules {
+    let mut interned_rules = UnfoldingRules::new();
+
+    for rule in unfolding_rules {
+        let lhs = graph.factory.intern(&*rule.lhs);
+        let rhs = graph.factory.intern(&*rule.rhs);
+
+        interned_rules.entry(lhs).or_default().push(rhs);
     }
 
-    new_abox.pairwise_different_individuals.push(new_individuals);
+    interned_rules
+}
+
 
-    if !is_at_least_concept_valid(&new_abox, &axiom.individual, &concept) {
-        new_abox.is_consistent = Some(false);
+/// Adds the definition of a named concept (or the negated definition of a negated one)
+/// to the label, where the concept has appeared
+fn apply_unfolding_rule(graph: &mut CompletionGraph, unfolding_rules: &UnfoldingRules) -> bool {
+    graph.skip_done(RuleType::Unfolding, |graph, x, c| {
+        unfolding_rules.get(&c).is_none_or(|rhs| rhs.iter().all(|&d| graph.has_concept(x, d)))
+    });
+
+    let expansion = graph.find_concepts(RuleType::Unfolding, |_| Some(()))
+        .into_iter()
+        .find_map(|(x, c, _)| {
+            let rhs = unfolding_rules.get(&c)?.iter().find(|&&rhs| !graph.has_concept(x, rhs))?;
+            Some((x.clone(), c, *rhs, graph.concept_dependencies(x, c)))
+        });
+
+    if expansion.is_none() {
+        debug!("There is nothing to unfold.");
+        return false;
     }
 
-    Some(new_abox)
+    let (x, c, rhs, dependencies) = expansion.unwrap();
+
+    debug!("Unfolding ({})[{}] => ({})[{}]", graph.factory.concept(c), x, graph.factory.concept(rhs), x);
+    graph.add_concept(&x, rhs, &dependencies);
+    true
 }
 
 
-fn apply_at_most_rule(abox: &ABox) -> Vec<ABox> {
-    let at_most_axioms = extract_concept_axioms(abox, ConceptType::AtMost);
+/// Expands a disjunction, which has none of its disjuncts in the label.
+/// The disjuncts, which negations are in the label, would clash right away, so we do not try them.
+/// If there is a single disjunct left, it is added deterministically (boolean constraint propagation),
+/// that's why such disjunctions go first. Otherwise we take the oldest one (or the smallest one, if the strategy says so).
+fn apply_disjunction_rule(graph: &mut CompletionGraph, strategy: ExpansionStrategy, statistics: &mut TableauStatistics) -> Vec<Choice> {
+    let is_satisfied = |graph: &CompletionGraph, x: &Individual, c: ConceptId| match graph.factory.node(c) {
+        ConceptNode::Or(cs) => cs.iter().any(|&sc| graph.has_concept(x, sc)),
+        _ => true
+    };
+    graph.skip_done(RuleType::Disjunction, is_satisfied);
+
+    let disjunctions = graph.find_concepts(RuleType::Disjunction, |node| match node { ConceptNode::Or(cs) => Some(cs.clone()), _ => None })
+        .into_iter()
+        .filter(|(x, _, subconcepts)| !subconcepts.iter().any(|&sc| graph.has_concept(x, sc)))
+        .map(|(x, c, subconcepts)| (x.clone(), c, subconcepts))
+        .collect::<Vec<(Individual, ConceptId, Vec<ConceptId>)>>();
+    let mut expansion = None;
+
+    for (x, c, subconcepts) in disjunctions {
+        // The remaining disjuncts are possible only because of the negations of the others
+        let mut dependencies = graph.concept_dependencies(&x, c);
+        let mut open_subconcepts = vec![];
+        let num_subconcepts = subconcepts.len();
+
+        for sc in subconcepts {
+            let negation = graph.factory.negation(sc);
+
+            match graph.has_concept(&x, negation) {
+                true => dependencies.extend(graph.concept_dependencies(&x, negation)),
+                false => open_subconcepts.push(sc)
+            }
+        }
+
+        let num_closed = num_subconcepts - open_subconcepts.len();
+
+        if open_subconcepts.len() <= 1 {
+            statistics.num_propagations += 1;
+            statistics.num_saved_branches += num_closed;
+            debug!("Propagated ({})[{}]", graph.factory.concept(c), x);
 
-    if at_most_axioms.is_empty() {
-        debug!("Tried to expand AtMost rule, but there are no relevant axioms.");
+            return match open_subconcepts.pop() {
+                Some(sc) => vec![Choice::AddConcepts(x, vec![sc], dependencies)],
+                None => vec![Choice::Clash(dependencies)]
+            };
+        }
+
+        let is_smaller = |(_, _, subconcepts, _, _): &(Individual, ConceptId, Vec<ConceptId>, DependencySet, usize)| {
+            strategy.smallest_disjunction_first && open_subconcepts.len() < subconcepts.len()
+        };
+
+        if expansion.as_ref().is_none_or(is_smaller) {
+            expansion = Some((x, c, open_subconcepts, dependencies, num_closed));
+        }
+    }
+
+    if expansion.is_none() {
+        debug!("All OR axioms are non-expandable.");
         return vec![];
     }
 
-    for axiom in at_most_axioms {
-        let concept = axiom.concept.downcast_ref::<AtMostConcept>().unwrap();
-        let others = extract_rhs_for_relation(&concept.relation, &axiom.individual, abox);
-        let others_with_concept = filter_by_concept(others, &concept.subconcept, abox);
+    // Semantic branching: the branches are tried from the last one, and when the disjunct C has failed,
+    // the next branches get "not C", so that they do not explore C again
+    let (x, c, subconcepts, dependencies, num_closed) = expansion.unwrap();
+    let negations = subconcepts.iter().map(|&sc| graph.factory.negation(sc)).collect::<Vec<ConceptId>>();
 
-        if others_with_concept.len() < concept.amount + 1 {
-            continue;
+    statistics.num_saved_branches += num_closed;
+    debug!("Successfully expanded OR rule: ({})[{}]", graph.factory.concept(c), x);
+
+    subconcepts
+        .iter()
+        .enumerate()
+        .map(|(i, &sc)| Choice::AddConcepts(x.clone(), Some(sc).into_iter().chain(negations[i + 1..].iter().cloned()).collect(), dependencies.clone()))
+        .collect()
+}
+
+
+/// Merges an individual x into a, if x belongs to the nominal {a}.
+/// For "oneOf (a b c)" we do not know which one is meant, so we branch over {a}, {b} and {c}.
+/// Negated nominals are checked here as well, since they can get violated by any merge.
+fn apply_nominal_rule(graph: &mut CompletionGraph) -> Vec<Choice> {
+    if let Some(dependencies) = find_negated_nominal_violation(graph) {
+        debug!("Obtained an inconsistency with a negated nominal.");
+        return vec![Choice::Clash(dependencies)];
+    }
+
+    let expansion = graph.find_concepts(RuleType::Nominal, |node| match node { ConceptNode::Nominal(individuals) => Some(individuals.clone()), _ => None })
+        .into_iter()
+        .find_map(|(x, c, nominal_individuals)| {
+            let individuals = nominal_individuals.iter()
+                .map(|a| graph.find_replacement(a))
+                .collect::<Vec<Individual>>();
+
+            if individuals.contains(x) {
+                return None; // Already satisfied
+            }
+
+            let is_chosen = |a: &Individual| graph.factory.find(&ConceptNode::Nominal(vec![a.clone()])).is_some_and(|n| graph.has_concept(x, n));
+
+            if individuals.len() > 1 && individuals.iter().any(is_chosen) {
+                return None; // One of the choices has already been made
+            }
+
+            // The nominal individuals could have been merged into the other ones, so we depend on that too
+            let mut dependencies = graph.concept_dependencies(x, c);
+            dependencies.extend(nominal_individuals.iter().flat_map(|a| graph.replacement_dependencies(a)));
+
+            Some((x.clone(), individuals, dependencies))
+        });
+
+    if expansion.is_none() {
+        debug!("All nominal axioms are non-expandable.");
+        return vec![];
+    }
+
+    let (x, individuals, dependencies) = expansion.unwrap();
+
+    if individuals.len() == 1 {
+        if !graph.contains_individual(&individuals[0]) {
+            graph.add_individual(individuals[0].clone());
         }
 
-        let mut replacements = HashMap::new();
-
-        for y in others_with_concept.clone() {
-            // Finding those individuals that do not have y ≠ z relation
-            // Since such relations are stored in abox.pairwise_different_individuals field
-            // We should iterate over them all to check if there both y and z are in the same set
-            // If there is not such a set, then we can use y to replace z
-            // and symmetrically replace z instead of y
-            // (but this latter case will be caught in an outer loop iteration for z)
-            let can_be_equal_to_y = others_with_concept.clone().into_iter()
-                .filter(|z| z.name != y.name)
-                .filter(|z| {
-                    abox.pairwise_different_individuals
-                        .iter()
-                        .find(|xs| {xs.contains(&y) && xs.contains(&z)})
-                        .is_none()
-                }).collect::<Vec<Individual>>();
-
-            if !can_be_equal_to_y.is_empty() {
-                replacements.insert(y, can_be_equal_to_y);
+        debug!("Successfully expanded nominal rule: merging {} into {}", x, individuals[0]);
+        return vec![Choice::Merge(x, individuals[0].clone(), dependencies)];
+    }
+
+    debug!("Successfully expanded nominal rule for {}", x);
+    individuals
+        .into_iter()
+        .map(|a| Choice::AddConcepts(x.clone(), vec![graph.factory.intern_node(ConceptNode::Nominal(vec![a]))], dependencies.clone()))
+        .collect()
+}
+
+
+fn find_negated_nominal_violation(graph: &CompletionGraph) -> Option<DependencySet> {
+    // x: not {a b c} is violated if x has been merged into one of a, b, c (or vice versa)
+    graph.find_concepts(RuleType::NegatedNominal, |node| match node { &ConceptNode::Not(c) => Some(c), _ => None })
+        .into_iter()
+        .filter_map(|(x, c, subconcept)| {
+            let nominal_individuals = match graph.factory.node(subconcept) {
+                ConceptNode::Nominal(individuals) => individuals,
+                _ => return None
+            };
+            let a = nominal_individuals.iter().find(|a| &graph.find_replacement(a) == x)?;
+            let mut dependencies = graph.concept_dependencies(x, c);
+
+            dependencies.extend(graph.replacement_dependencies(a));
+            Some(dependencies)
+        })
+        .next()
+}
+
+
+fn apply_only_rule(graph: &mut CompletionGraph, rbox: &RBox) -> bool {
+    let expansion = graph.find_concepts(RuleType::Only, |node| match node { ConceptNode::Only(r, c) => Some((r.clone(), *c)), _ => None })
+        .into_iter()
+        .find_map(|(x, c, (relation, subconcept))| {
+            // ∀⁺-rule: for every transitive s ⊑ r, "only s C" should hold for the s-neighbours too,
+            // so that "only r C" propagates along s-chains
+            let propagated_concepts = rbox.transitive_subrelations(&relation)
+                .into_iter()
+                .flat_map(|s| {
+                    extract_rhs_for_relation(&s, x, graph, rbox)
+                        .into_iter()
+                        .map(move |y| (y, ConceptNode::Only(s.clone(), subconcept)))
+                });
+
+            let (y, new_concept) = extract_rhs_for_relation(&relation, x, graph, rbox)
+                .into_iter()
+                .map(|y| (y, graph.factory.node(subconcept).clone()))
+                .chain(propagated_concepts)
+                .find(|(y, new_concept)| !graph.factory.find(new_concept).is_some_and(|n| graph.has_concept(y, n)))?;
+
+            // The new concept depends on the edge to its individual as well
+            let mut dependencies = graph.concept_dependencies(x, c);
+            dependencies.extend(graph.edge_dependencies(x, &y));
+
+            Some((x.clone(), c, y, new_concept, dependencies))
+        });
+
+    if expansion.is_none() {
+        debug!("All ONLY axioms are non-expandable.");
+        return false;
+    }
+
+    let (x, c, y, new_concept, dependencies) = expansion.unwrap();
+    let new_concept = graph.factory.intern_node(new_concept);
+
+    debug!("Successfully expanded ONLY rule: ({})[{}] => ({})[{}]", graph.factory.concept(c), x, graph.factory.concept(new_concept), y);
+    graph.add_concept(&y, new_concept, &dependencies);
+    true
+}
+
+
+fn apply_some_rule(graph: &mut CompletionGraph, rbox: &RBox, cache: &mut SatisfiabilityCache, statistics: &mut TableauStatistics) -> bool {
+    // Blockers are only needed for the concepts, which are not satisfied yet
+    let mut blockers = None;
+    let expansion = graph.find_concepts(RuleType::Some, |node| match node { ConceptNode::Some(r, c) => Some((r.clone(), *c)), _ => None })
+        .into_iter()
+        .find(|(x, c, (relation, subconcept))| {
+            if extract_rhs_for_relation(relation, x, graph, rbox).iter().any(|y| graph.has_concept(y, *subconcept)) {
+                return false;
             }
+
+            match blockers.get_or_insert_with(|| find_blockers(graph)).get(*x) {
+                Some(blocker) => {
+                    debug!("Tried to expand ({})[{}], but it is blocked by {}.", graph.factory.concept(*c), x, blocker);
+                    false
+                },
+                None => true
+            }
+        })
+        .map(|(x, c, concept)| (x.clone(), concept, graph.concept_dependencies(x, c)));
+
+    if expansion.is_none() {
+        debug!("All SOME axioms are non-expandable.");
+        return false;
+    }
+
+    let (x, (relation, subconcept), dependencies) = expansion.unwrap();
+    let is_satisfiable = find_cached_satisfiability(graph, &x, &relation, subconcept, &dependencies, rbox, cache);
+
+    if is_satisfiable.is_some() {
+        statistics.num_cache_hits += 1;
+    }
+
+    if is_satisfiable == Some(false) {
+        return true;
+    }
+
+    let new_individual = graph.create_new_individual();
+    debug!("Creating new individual: {}", new_individual.name);
+
+    graph.add_edge(&x, &new_individual, &relation, &dependencies);
+    graph.add_concept(&new_individual, subconcept, &dependencies);
+
+    if is_satisfiable == Some(true) {
+        graph.set_cached(&new_individual);
+    }
+
+    debug!("Successfully expanded SOME rule for {}", x);
+    true
+}
+
+
+fn apply_at_least_rule(graph: &mut CompletionGraph, rbox: &RBox, cache: &mut SatisfiabilityCache, statistics: &mut TableauStatistics) -> bool {
+    // Blockers are only needed for the concepts, which are not satisfied yet
+    let mut blockers = None;
+    let expansion = graph.find_concepts(RuleType::AtLeast, |node| match node { ConceptNode::AtLeast(n, r, c) => Some((*n, r.clone(), *c)), _ => None })
+        .into_iter()
+        .find(|(x, c, (amount, relation, subconcept))| {
+            let possible_rhs: HashSet<Individual> = HashSet::from_iter(extract_rhs_for_relation(relation, x, graph, rbox));
+
+            // Searching for a set of pairwise different individuals that would satisfy the constraints
+            if graph.different_individuals().iter().any(|diff_individuals| {
+                diff_individuals.len() >= *amount && diff_individuals.iter().all(|y| {
+                    possible_rhs.contains(y) && graph.has_concept(y, *subconcept)
+                })
+            }) {
+                return false;
+            }
+
+            match blockers.get_or_insert_with(|| find_blockers(graph)).get(*x) {
+                Some(blocker) => {
+                    debug!("Tried to expand ({})[{}], but it is blocked by {}.", graph.factory.concept(*c), x, blocker);
+                    false
+                },
+                None => true
+            }
+        })
+        .map(|(x, c, concept)| (x.clone(), concept, graph.concept_dependencies(x, c)));
+
+    if expansion.is_none() {
+        debug!("Tried to expand AtLeast rule, but possible expansions are already in the graph.");
+        return false;
+    }
+
+    let (x, (amount, relation, subconcept), dependencies) = expansion.unwrap();
+    let is_satisfiable = match amount {
+        0 => None,
+        _ => find_cached_satisfiability(graph, &x, &relation, subconcept, &dependencies, rbox, cache)
+    };
+
+    if is_satisfiable.is_some() {
+        statistics.num_cache_hits += 1;
+    }
+
+    if is_satisfiable == Some(false) {
+        return true;
+    }
+
+    let mut new_individuals = HashSet::new();
+
+    for _ in 0..amount {
+        let new_individual = graph.create_new_individual();
+        debug!("Creating new individual: {}", new_individual.name);
+
+        graph.add_edge(&x, &new_individual, &relation, &dependencies);
+        graph.add_concept(&new_individual, subconcept, &dependencies);
+
+        if is_satisfiable == Some(true) {
+            graph.set_cached(&new_individual);
         }
 
-        if replacements.len() < concept.amount + 1 {
+        new_individuals.insert(new_individual);
+    }
+
+    graph.add_different_individuals(new_individuals, &dependencies);
+
+    if let Some(mut clash_dependencies) = find_smaller_at_most_dependencies(graph, &x, amount, &relation, subconcept) {
+        clash_dependencies.extend(dependencies);
+        graph.set_clash(&clash_dependencies);
+    }
+
+    true
+}
+
+
+fn apply_at_most_rule(graph: &CompletionGraph, rbox: &RBox) -> Vec<Choice> {
+    for (x, c, (amount, relation, subconcept)) in graph.find_concepts(RuleType::AtMost, at_most_parts) {
+        let others = extract_rhs_for_relation(&relation, x, graph, rbox);
+        let others_with_concept = filter_by_concept(others, subconcept, graph);
+
+        if others_with_concept.len() < amount + 1 {
             continue;
         }
 
-        debug!("We have found an AtMost axiom, which can be expanded: {}", axiom);
+        debug!("We have found an AtMost axiom, which can be expanded: ({})[{}]", graph.factory.concept(c), x);
+
+        // Any n+1 of the neighbours are enough: two of them must be equal.
+        // We can merge y into z if we do not know that y ≠ z.
+        // Younger individuals are merged into older ones, so that named individuals are kept
+        // and the predecessors of generated individuals do not change.
+        // The merge is forced by the at-most concept and the fact, that y and z are its C-neighbours.
+        let candidates = &others_with_concept[..amount + 1];
+        let mut dependencies = graph.concept_dependencies(x, c);
+
+        for y in candidates {
+            dependencies.extend(graph.edge_dependencies(x, y));
+            dependencies.extend(graph.concept_dependencies(y, subconcept));
+        }
 
-        // Now, we should keep only n+1 replacements (knowing, that inequality relation is symmetric)
-        let mut variables_to_keep: HashSet<Individual> = HashSet::from_iter(replacements
-            .keys().map(|k| k.clone()).collect::<Vec<Individual>>()[..concept.amount+1].iter().cloned());
+        let mut choices = vec![];
 
-        for x_old in replacements.clone().keys() {
-            if variables_to_keep.contains(x_old) {
-                let replacements_for_x_old = replacements[x_old]
-                    .clone()
-                    .into_iter()
-                    .filter(|x| variables_to_keep.contains(&x))
-                    .collect();
+        for y in candidates {
+            for z in candidates {
+                let can_be_merged = y != z && !z.is_younger(y) && (y.is_younger(z) || y.name < z.name);
 
-                replacements.insert(x_old.clone(), replacements_for_x_old);
-            } else {
-                replacements.remove(x_old);
+                if can_be_merged && !graph.are_different(y, z) {
+                    debug!("Replacing {} with {}", y, z);
+                    choices.push(Choice::Merge(y.clone(), z.clone(), dependencies.clone()));
+                }
             }
         }
 
-        let mut new_aboxes = vec![];
+        if choices.is_empty() {
+            // All the neighbours are pairwise different, so we cannot satisfy the at-most concept
+            debug!("Obtained an inconsistency while expanding AtMost rule: ({})[{}]", graph.factory.concept(c), x);
 
-        for (x_old, xs_new) in replacements {
-            for x_new in xs_new {
-                debug!("Replacing {} with {}", x_old, x_new);
-                new_aboxes.push(replace_individual_in_abox(abox, x_old.clone(), x_new));
+            for y in candidates {
+                for z in candidates {
+                    dependencies.extend(graph.difference_dependencies(y, z));
+                }
             }
-        }
 
-        debug_assert!(new_aboxes.len() > 0);
+            choices.push(Choice::Clash(dependencies));
+        }
 
-        return new_aboxes;
+        return choices;
     }
 
-    debug!("Tried to expand AtMost rule, but all possible expansions are already in ABox.");
+    debug!("Tried to expand AtMost rule, but all possible expansions are already in the graph.");
     vec![]
 }
 
 
-fn apply_choose_rule(abox: &ABox) -> Vec<ABox> {
-    let at_most_axioms = extract_concept_axioms(abox, ConceptType::AtMost);
-
-    if at_most_axioms.is_empty() {
-        debug!("Tried to apply choose rule, but there are no relevant axioms.");
-        return vec![];
+fn apply_choose_rule(graph: &mut CompletionGraph, rbox: &RBox) -> Vec<Choice> {
+    let at_most_concepts = graph.find_concepts(RuleType::AtMost, at_most_parts)
+        .into_iter()
+        .map(|(x, c, parts)| (x.clone(), c, parts))
+        .collect::<Vec<_>>();
+
+    for (x, c, (_, relation, subconcept)) in at_most_concepts {
+        let negated_subconcept = graph.factory.negation(subconcept);
+
+        for y in extract_rhs_for_relation(&relation, &x, graph, rbox) {
+            if !graph.has_concept(&y, subconcept) && !graph.has_concept(&y, negated_subconcept) {
+                let mut dependencies = graph.concept_dependencies(&x, c);
+                dependencies.extend(graph.edge_dependencies(&x, &y));
+
+                debug!("Successfully appled choose-rule for axiom ({})[{}] and individual {}", graph.factory.concept(c), x, y);
+                return vec![
+                    Choice::AddConcepts(y.clone(), vec![subconcept], dependencies.clone()),
+                    Choice::AddConcepts(y, vec![negated_subconcept], dependencies)
+                ];
+            }
+        }
     }
 
-    for axiom in at_most_axioms {
-        let concept = axiom.concept.downcast_ref::<AtMostConcept>().unwrap();
-        let others = extract_rhs_for_relation(&concept.relation, &axiom.individual, abox);
+    vec![]
+}
 
-        for y in others {
-            let y_concept = Box::new(ConceptAxiom {
-                individual: y.clone(),
-                concept: concept.subconcept.clone()
-            }) as Box<dyn ABoxAxiom>;
 
-            let y_not_concept = Box::new(ConceptAxiom {
-                individual: y.clone(),
-                concept: concept.subconcept.negate().convert_to_nnf()
-            }) as Box<dyn ABoxAxiom>;
+fn at_most_parts(node: &ConceptNode) -> Option<(usize, Relation, ConceptId)> {
+    match node {
+        ConceptNode::AtMost(n, r, c) => Some((*n, r.clone(), *c)),
+        _ => None
+    }
+}
 
-            if !abox.axioms.contains(&y_concept) && !abox.axioms.contains(&y_not_concept) {
-                let mut new_abox_y = abox.clone();
-                let mut new_abox_y_not = abox.clone();
 
-                new_abox_y.axioms.insert(y_concept);
-                new_abox_y_not.axioms.insert(y_not_concept);
+/// The label, which a fresh r-successor of x with C gets from the "only"-rule (and the ∀⁺-rule),
+/// along with the dependencies of the "only" concepts of x, which it comes from
+fn successor_label(graph: &mut CompletionGraph, x: &Individual, relation: &Relation, subconcept: ConceptId,
+                   rbox: &RBox) -> (Vec<ConceptId>, DependencySet) {
+    let only_concepts = graph.label(x)
+        .into_iter()
+        .filter_map(|c| match graph.factory.node(c) {
+            ConceptNode::Only(s, d) if rbox.is_subrelation(relation, s) => Some((c, s.clone(), *d)),
+            _ => None
+        })
+        .collect::<Vec<(ConceptId, Relation, ConceptId)>>();
+    let mut label = vec![subconcept];
+    let mut dependencies = DependencySet::new();
 
-                debug!("Successfully appled choose-rule for axiom {} and individual {}", axiom, y);
-                return vec![new_abox_y, new_abox_y_not];
-            }
+    for (c, s, d) in only_concepts {
+        label.push(d);
+        dependencies.extend(graph.concept_dependencies(x, c));
+
+        for t in rbox.transitive_subrelations(&s).into_iter().filter(|t| rbox.is_subrelation(relation, t)) {
+            label.push(graph.factory.intern_node(ConceptNode::Only(t, d)));
         }
     }
 
-    vec![]
+    (label_key(&graph.factory, &label), dependencies)
 }
 
 
-fn apply_gci_rule(abox: &ABox, super_concept: &Option<Box<dyn Concept>>) -> Option<ABox> {
-    if super_concept.is_none() {
-        debug!("Tried to apply GCI, but there is no GCI.");
+/// Looks up the label of a fresh successor in the cache. An unsatisfiable label is a clash for x right away.
+/// A satisfiable one is used only when the successor cannot affect x, then it does not have to be expanded.
+fn find_cached_satisfiability(graph: &mut CompletionGraph, x: &Individual, relation: &Relation, subconcept: ConceptId,
+                              dependencies: &DependencySet, rbox: &RBox, cache: &SatisfiabilityCache) -> Option<bool> {
+    let (label, mut clash_dependencies) = successor_label(graph, x, relation, subconcept, rbox);
+
+    match cache.is_satisfiable(&label) {
+        Some(false) => {
+            debug!("The successor of {} would get an unsatisfiable label (found in the cache)", x);
+            clash_dependencies.extend(dependencies.iter().cloned());
+            graph.set_clash(&clash_dependencies);
+            Some(false)
+        },
+        Some(true) if cache.reuse_satisfiable => {
+            debug!("The successor of {} gets a satisfiable label (found in the cache)", x);
+            Some(true)
+        },
+        _ => None
+    }
+}
+
+
+/// The ABox of a satisfiability check has a single individual with some concepts (and nothing else),
+/// so it is consistent iff the label of this individual is satisfiable.
+/// Nominals could refer to this individual by its name (and with the unique name assumption
+/// it differs from the other ones), so such labels are not the same as the labels of the fresh successors.
+fn find_concept_label(abox: &ABox, factory: &mut ConceptFactory) -> Option<Vec<ConceptId>> {
+    let is_concept_check = abox.individuals.len() == 1
+        && !abox.unique_name_assumption
+        && abox.is_consistent.is_none()
+        && abox.pairwise_different_individuals.is_empty()
+        && abox.replacements.is_empty()
+        && abox.axioms.iter().all(|a| a.axiom_type() == ABoxAxiomType::Concept);
+
+    if !is_concept_check {
         return None;
     }
-    let super_concept = super_concept.as_ref().unwrap();
 
-    for x in &abox.individuals {
-        let new_axiom = ConceptAxiom {
-            concept: super_concept.clone(),
-            individual: x.clone()
-        };
-        let new_axiom = Box::new(new_axiom) as Box<dyn ABoxAxiom>;
+    let label = abox.axioms
+        .iter()
+        .map(|a| factory.intern(&*a.downcast_ref::<ConceptAxiom>().unwrap().concept))
+        .collect::<Vec<ConceptId>>();
 
-        if !abox.axioms.contains(&new_axiom) {
-            let mut new_abox = abox.clone();
-            debug!("Found a variable to apply the GCI: {}", new_axiom);
-            new_abox.axioms.insert(new_axiom);
-            return Some(new_abox);
-        }
+    match label.iter().any(|&c| has_nominals(factory, c)) {
+        true => None,
+        false => Some(label_key(factory, &label))
     }
+}
 
-    debug!("Tried to apply GCI, but there are no expandable individuals.");
 
-    None
+fn has_nominal_axioms(super_concept: &Option<Box<dyn Concept>>, unfolding_rules: &[TBoxAxiom]) -> bool {
+    super_concept.iter()
+        .chain(unfolding_rules.iter().flat_map(|a| vec![&a.lhs, &a.rhs]))
+        .any(|c| has_nominal_concepts(&**c))
 }
 
 
-fn extract_concept_axioms<'a>(abox: &'a ABox, concept_type: ConceptType) -> Vec<&'a ConceptAxiom> {
-    abox.axioms
-        .iter()
-        .filter(|a| a.axiom_type() == ABoxAxiomType::Concept)
-        .map(|a| a.downcast_ref::<ConceptAxiom>().unwrap())
-        .filter(|a| a.concept.concept_type() == concept_type)
-        .collect::<Vec<&ConceptAxiom>>()
+fn has_nominal_concepts(concept: &dyn Concept) -> bool {
+    concept.concept_type() == ConceptType::Nominal || concept.children().into_iter().any(has_nominal_concepts)
 }
 
 
-fn create_new_axioms(concepts: Vec<Box<dyn Concept>>,
-                     individual: Individual, abox: &ABox) -> Vec<Box<dyn ABoxAxiom>> {
-    concepts
-        .into_iter()
-        // Convert to an axiom
-        .map(|sc| ConceptAxiom {concept: sc, individual: individual.clone() })
-        .map(|a| Box::new(a) as Box<dyn ABoxAxiom>)
-        // Remove axioms that we already have
-        .filter(|a| !abox.axioms.contains(&*a))
+fn has_nominals(factory: &ConceptFactory, concept: ConceptId) -> bool {
+    match factory.node(concept) {
+        ConceptNode::Nominal(_) => true,
+        ConceptNode::Top | ConceptNode::Bottom | ConceptNode::Atomic(_) => false,
+        ConceptNode::Not(c) | ConceptNode::Some(_, c) | ConceptNode::Only(_, c)
+            | ConceptNode::AtLeast(_, _, c) | ConceptNode::AtMost(_, _, c) => has_nominals(factory, *c),
+        ConceptNode::And(cs) | ConceptNode::Or(cs) => cs.iter().any(|&c| has_nominals(factory, c))
+    }
+}
+
+
+fn find_used_concepts(graph: &CompletionGraph, super_concept: Option<ConceptId>, unfolding_rules: &UnfoldingRules) -> Vec<ConceptId> {
+    // The concepts, which can appear in the labels, are the subconcepts of these ones (or their negations)
+    graph.individuals()
+        .iter()
+        .flat_map(|x| graph.label(x))
+        .chain(super_concept)
+        .chain(unfolding_rules.iter().flat_map(|(&lhs, rhs)| Some(lhs).into_iter().chain(rhs.iter().cloned())))
         .collect()
 }
 
 
-fn create_new_abox_from_concept_axiom(axiom: Box<dyn ABoxAxiom>, abox: &ABox) -> ABox {
-    debug_assert!(!abox.axioms.contains(&axiom));
+fn allows_cached_successors(factory: &ConceptFactory, concepts: &[ConceptId], rbox: &RBox, cache: &mut SatisfiabilityCache) -> bool {
+    // A successor can affect its predecessor through inverse relations, nominals and "at-most" restrictions
+    // (functional relations are "at-most 1" restrictions as well), so they should not appear anywhere
+    if !rbox.functional_relations().is_empty() || rbox.has_inverse_inclusions() {
+        return false;
+    }
+
+    concepts.iter().all(|&c| cache.is_simple(factory, c))
+}
+
+
+fn allows_model_merging(factory: &ConceptFactory, concepts: &[ConceptId], rbox: &RBox, cache: &mut SatisfiabilityCache) -> bool {
+    // "At-most" restrictions (and functional relations) are fine here, since pseudo-models take them into account
+    !rbox.has_inverse_inclusions() && concepts.iter().all(|&c| cache.is_mergeable(factory, c))
+}
+
+
+/// In a complete graph without a clash every "some" (and "at-least") concept of an individual, which is not blocked,
+/// has a successor with all the concepts a fresh successor would get, so these labels are satisfiable
+fn cache_satisfiable_successors(graph: &mut CompletionGraph, rbox: &RBox, cache: &mut SatisfiabilityCache) {
+    let blockers = find_blockers(graph);
+    let select = |node: &ConceptNode| match node {
+        ConceptNode::Some(r, c) => Some((r.clone(), *c)),
+        ConceptNode::AtLeast(n, r, c) if *n > 0 => Some((r.clone(), *c)),
+        _ => None
+    };
+    let successors = graph.find_concepts(RuleType::Some, select)
+        .into_iter()
+        .chain(graph.find_concepts(RuleType::AtLeast, select))
+        .filter(|(x, _, _)| !blockers.contains_key(*x))
+        .map(|(x, _, (relation, subconcept))| (x.clone(), relation, subconcept))
+        .collect::<Vec<(Individual, Relation, ConceptId)>>();
+
+    for (x, relation, subconcept) in successors {
+        let (label, _) = successor_label(graph, &x, &relation, subconcept, rbox);
+        cache.insert(label, true);
+    }
+}
 
-    let mut new_abox = abox.clone();
-    let concept_axiom = axiom.downcast_ref::<ConceptAxiom>().unwrap();
-    let negated_axiom = Box::new(ConceptAxiom {
-        concept: concept_axiom.concept.negate(),
-        individual: concept_axiom.individual.clone()
-    }) as Box<dyn ABoxAxiom>;
 
-    new_abox.axioms.insert(axiom);
+fn apply_gci_rule(graph: &mut CompletionGraph, super_concept: Option<ConceptId>) -> bool {
+    if super_concept.is_none() {
+        debug!("Tried to apply GCI, but there is no GCI.");
+        return false;
+    }
+    let super_concept = super_concept.unwrap();
+    let x = graph.individuals().iter().find(|x| !graph.has_concept(x, super_concept)).cloned();
 
-    if new_abox.axioms.contains(&negated_axiom) {
-        new_abox.is_consistent = Some(false);
+    if x.is_none() {
+        debug!("Tried to apply GCI, but there are no expandable individuals.");
+        return false;
     }
 
-    new_abox
+    debug!("Found a variable to apply the GCI: {}", x.as_ref().unwrap());
+    graph.add_concept(&x.unwrap(), super_concept, &DependencySet::new());
+    true
 }
 
 
-fn find_blocker(abox: &ABox, y: &Individual) -> Option<Individual> {
-    /// Checks if the individual y is blocked by some other individual
-    abox.individuals
-        .clone()
-        .into_iter()
-        .filter(|x| &x != &y)
-        .find(|x| is_blocking(abox, x, y))
+/// Finds the blocked individuals along with their blockers: y is blocked either directly
+/// by some individual, which is not blocked itself, or because one of its predecessors is blocked.
+/// Since the ∀⁺-rule pushes "only s C" down the s-chains, labels along a chain of a transitive
+/// relation stop changing at some point, and equality-based blocking cuts such chains.
+/// Both the blockers and the predecessors are older than y, so a single pass from the oldest individuals is enough.
+fn find_blockers(graph: &CompletionGraph) -> HashMap<Individual, Individual> {
+    let mut individuals = graph.individuals().iter().filter(|x| x.is_generated()).collect::<Vec<&Individual>>();
+    individuals.sort_by(|x, y| x.is_younger(y).cmp(&y.is_younger(x)));
+    let mut blockers: HashMap<Individual, Individual> = HashMap::new();
+
+    for (i, &y) in individuals.iter().enumerate() {
+        let blocker = individuals[..i]
+            .iter()
+            .find(|x| !blockers.contains_key(**x) && is_blocking(graph, x, y))
+            .map(|&x| x.clone())
+            .or_else(|| find_predecessor(graph, y).and_then(|p| blockers.get(&p).cloned()));
+
+        if let Some(blocker) = blocker {
+            blockers.insert(y.clone(), blocker);
+        }
+    }
+
+    blockers
 }
 
 
-fn is_blocking(abox: &ABox, lhs: &Individual, rhs: &Individual) -> bool {
-    // Checks if the individual rhs is blocked by an individual lhs
+fn is_blocking(graph: &CompletionGraph, lhs: &Individual, rhs: &Individual) -> bool {
+    // Checks if the individual rhs is blocked by an individual lhs.
+    // With inverse relations we need pairwise blocking: lhs and rhs should have the same labels,
+    // their predecessors should have the same labels and the edges from the predecessors should match.
     // If lhs is younger, then it cannot block rhs
-    !lhs.is_younger(rhs) && abox.axioms
-        .iter()
-        .filter(|a| a.axiom_type() == ABoxAxiomType::Concept)
-        .map(|a| {a.downcast_ref::<ConceptAxiom>().unwrap()})
-        .filter(|ca| &ca.individual == rhs)
-        .map(|ca| Box::new(ConceptAxiom {
-            concept: ca.concept.clone(),
-            individual: lhs.clone()
-        }) as Box<dyn ABoxAxiom>)
-        .all(|ca| abox.axioms.contains(&ca))
+    if lhs.is_younger(rhs) {
+        return false;
+    }
+
+    match (find_predecessor(graph, lhs), find_predecessor(graph, rhs)) {
+        (Some(lhs_pred), Some(rhs_pred)) => {
+            graph.have_same_labels(lhs, rhs)
+                && graph.have_same_labels(&lhs_pred, &rhs_pred)
+                && graph.edge_label(&lhs_pred, lhs) == graph.edge_label(&rhs_pred, rhs)
+        },
+        _ => false
+    }
 }
 
 
-fn extract_rhs_for_relation(relation: &Relation, individual: &Individual, abox: &ABox) -> Vec<Individual> {
-    abox.axioms
-        .iter()
-        .filter(|a| a.axiom_type() == ABoxAxiomType::Relation)
-        .map(|a| a.downcast_ref::<RelationAxiom>().unwrap())
-        .filter(|ra| &ra.relation == relation && &ra.lhs == individual)
-        .map(|ra| ra.rhs.clone())
-        .collect::<Vec<Individual>>()
+fn find_predecessor(graph: &CompletionGraph, x: &Individual) -> Option<Individual> {
+    // Generated individuals are created as successors of older ones,
+    // that's why the predecessor is an older individual with an edge to x
+    if !x.is_generated() {
+        return None;
+    }
+
+    graph.neighbours(x)
+        .into_iter()
+        .find(|y| y != x && !y.is_younger(x) && !graph.edge(y, x).is_empty())
+}
+
+
+fn extract_rhs_for_relation(relation: &Relation, individual: &Individual, graph: &CompletionGraph, rbox: &RBox) -> Vec<Individual> {
+    // Returns all the relation-neighbours of the individual, i.e. such y,
+    // that either s(x, y) or s-(y, x) is in the graph for some subrelation s of r
+    graph.neighbours(individual)
+        .into_iter()
+        .filter(|y| {
+            graph.edge(individual, y).iter().any(|s| rbox.is_subrelation(s, relation))
+                || graph.edge(y, individual).iter().any(|s| rbox.is_subrelation(&s.inverse(), relation))
+        })
+        .collect()
 }
 
 
-fn is_at_least_concept_valid(abox: &ABox, individual: &Individual, concept: &AtLeastConcept) -> bool {
+fn find_smaller_at_most_dependencies(graph: &CompletionGraph, x: &Individual, amount: usize,
+                                     relation: &Relation, subconcept: ConceptId) -> Option<DependencySet> {
     // at_least concept is valid if there is no at_most concept with the smaller amount
-    (1..concept.amount).find(|&n| {
-        abox.axioms.contains(&(Box::new(ConceptAxiom {
-            concept: Box::new(AtMostConcept {
-                subconcept: concept.subconcept.clone(),
-                relation: concept.relation.clone(),
-                amount: n,
-            }) as Box<dyn Concept>,
-            individual: individual.clone()
-        }) as Box<dyn ABoxAxiom>))
-    }).is_none()
+    (1..amount)
+        .filter_map(|n| graph.factory.find(&ConceptNode::AtMost(n, relation.clone(), subconcept)))
+        .find(|&at_most| graph.has_concept(x, at_most))
+        .map(|at_most| graph.concept_dependencies(x, at_most))
 }
 
 
 fn filter_by_concept(individuals: Vec<Individual>,
-                     concept: &Box<dyn Concept>, abox: &ABox) -> Vec<Individual> {
+                     concept: ConceptId, graph: &CompletionGraph) -> Vec<Individual> {
     individuals
         .into_iter()
-        .filter(|x| {
-            abox.axioms.contains(&(Box::new(ConceptAxiom {
-                individual: x.clone(),
-                concept: concept.clone()
-            }) as Box<dyn ABoxAxiom>))
-        })
+        .filter(|x| graph.has_concept(x, concept))
         .collect()
 }
 
 
-fn replace_individual_in_abox(abox: &ABox, x_old: Individual, x_new: Individual) -> ABox {
-    debug_assert!(abox.individuals.contains(&x_old));
-    debug_assert!(abox.individuals.contains(&x_new));
+fn merge_individuals(graph: &mut CompletionGraph, x_old: &Individual, x_new: &Individual,
+                     dependencies: &DependencySet, rbox: &RBox) {
+    // x_new has got all the concepts and edges of x_old, so they may clash now
+    // (concept clashes are found by the graph, but the negated relations need the RBox)
+    graph.merge(x_old, x_new, dependencies);
 
-    let mut new_abox = abox.clone();
+    if let Some(clash_dependencies) = find_negative_relation_clash(graph, rbox) {
+        graph.set_clash(&clash_dependencies);
+    }
+}
 
-    new_abox.axioms = HashSet::from_iter(new_abox.axioms.into_iter().map(|a| {
-        match a.axiom_type() {
-            ABoxAxiomType::Concept => {
-                let concept_axiom = a.downcast_ref::<ConceptAxiom>().unwrap();
 
-                if concept_axiom.individual != x_old {
-                    a
-                } else {
-                    Box::new(ConceptAxiom {
-                        concept: concept_axiom.concept.clone(),
-                        individual: x_new.clone()
-                    })
-                }
-            },
-            ABoxAxiomType::Relation => {
-                let relation_axiom = a.downcast_ref::<RelationAxiom>().unwrap();
-
-                if relation_axiom.lhs != x_old && relation_axiom.rhs != x_old {
-                    a
-                } else if relation_axiom.lhs == x_old {
-                    Box::new(RelationAxiom {
-                        lhs: x_new.clone(),
-                        rhs: relation_axiom.rhs.clone(),
-                        relation: relation_axiom.relation.clone()
-                    })
-                } else if relation_axiom.rhs == x_old {
-                    Box::new(RelationAxiom {
-                        lhs: relation_axiom.lhs.clone(),
-                        rhs: x_new.clone(),
-                        relation: relation_axiom.relation.clone()
-                    })
-                } else {
-                    unreachable!();
+fn find_negative_relation_clash(graph: &CompletionGraph, rbox: &RBox) -> Option<DependencySet> {
+    // The path, which relates x and y, can be long, so we blame all the edges for simplicity
+    graph.negative_edges()
+        .into_iter()
+        .find(|(x, y, relation, _)| is_related(graph, x, y, relation, rbox))
+        .map(|(_, _, _, dependencies)| {
+            let mut dependencies = dependencies.clone();
+            dependencies.extend(graph.all_edge_dependencies());
+            dependencies
+        })
+}
+
+
+/// Checks if r(x, y) follows from the edges: either there is an s-edge for some s ⊑ r,
+/// or there is a chain of t-edges for some transitive t ⊑ r
+fn is_related(graph: &CompletionGraph, x: &Individual, y: &Individual, relation: &Relation, rbox: &RBox) -> bool {
+    if extract_rhs_for_relation(relation, x, graph, rbox).contains(y) {
+        return true;
+    }
+
+    rbox.transitive_subrelations(relation).iter().any(|t| {
+        let mut visited = vec![x.clone()];
+        let mut queue = vec![x.clone()];
+
+        while let Some(z) = queue.pop() {
+            for neighbour in extract_rhs_for_relation(t, &z, graph, rbox) {
+                if &neighbour == y {
+                    return true;
+                } else if !visited.contains(&neighbour) {
+                    visited.push(neighbour.clone());
+                    queue.push(neighbour);
                 }
             }
         }
-    }));
 
-    new_abox.individuals = HashSet::from_iter(new_abox.individuals
-        .into_iter().filter(|x| x.name != x_old.name));
+        false
+    })
+}
 
-    for pairwise_diffs in &mut new_abox.pairwise_different_individuals {
-        if !pairwise_diffs.contains(&x_old) {
-            continue;
-        }
 
-        pairwise_diffs.remove(&x_old);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use error::CyclicDefinitionError;
 
-        if pairwise_diffs.contains(&x_new) {
-            new_abox.is_consistent = Some(false);
-        } else {
-            pairwise_diffs.insert(x_new.clone());
-        }
+    fn run_with_statistics(abox: &str, tbox: &str, strategy: ExpansionStrategy) -> (Option<ABox>, TableauStatistics) {
+        let reasoner = Reasoner::new(KnowledgeBase::parse(abox, tbox).unwrap()).unwrap().with_strategy(strategy);
+        tableau_reasoning_with_statistics(reasoner.abox.clone(), reasoner.super_concept.clone(),
+                                          &reasoner.unfolding_rules, &reasoner.tbox.rbox, reasoner.strategy)
     }
 
-    new_abox.replacements.insert(x_old, x_new);
+    fn is_consistent(abox: &str, tbox: &str) -> bool {
+        Reasoner::new(KnowledgeBase::parse(abox, tbox).unwrap()).unwrap().is_consistent()
+    }
+
+    fn is_subsumed(reasoner: &Reasoner, sub: &str, sup: &str) -> bool {
+        reasoner.is_subsumed(&*parse_concept(sub).unwrap(), &*parse_concept(sup).unwrap())
+    }
+
+    #[test]
+    fn test_consistency() {
+        let abox = "hasChild[joe, ann]\nhasChild[joe, eva]\nhasChild[joe, mary]\nParentWithMax2Children[joe]";
+        let tbox = "ParentWithMax2Children == (<= 2 hasChild __TOP__)";
+        assert!(Reasoner::new(KnowledgeBase::parse(abox, tbox).unwrap()).unwrap().is_consistent());
+
+        let abox = "(not (some r (or ((and ((A) (some r A))) (and ((not A) (some r (some r (not A)))))))))[a]\n\
+                    r[a,b]\nr[b,d]\nr[d,c]\nr[a,c]\nr[c,d]\nA[d]";
+        assert!(!Reasoner::new(KnowledgeBase::parse(abox, "").unwrap()).unwrap().is_consistent());
+    }
+
+    #[test]
+    fn test_subsumption() {
+        let tbox = parse_tbox("Parent == some hasChild Human\nMother == and (Parent Female)").unwrap();
+        let reasoner = Reasoner::new(KnowledgeBase::from_tbox(tbox)).unwrap();
+
+        assert!(reasoner.is_subsumed(&*parse_concept("Mother").unwrap(), &*parse_concept("Parent").unwrap()));
+        assert!(reasoner.is_subsumed(&*parse_concept("Mother").unwrap(), &*parse_concept("some hasChild __TOP__").unwrap()));
+        assert!(!reasoner.is_subsumed(&*parse_concept("Parent").unwrap(), &*parse_concept("Mother").unwrap()));
+    }
+
+    #[test]
+    fn test_told_subsumption() {
+        let tbox = parse_tbox("Mother == and (Parent Female)\nParent -> Human\nFemale -> not Male\nA -> and (B (not Male))\nB -> Male").unwrap();
+        let reasoner = Reasoner::new(KnowledgeBase::from_tbox(tbox)).unwrap();
+        let concept = |s: &str| parse_concept(s).unwrap();
+
+        assert!(reasoner.is_subsumed(&*concept("Mother"), &*concept("Human")));
+        assert!(reasoner.find_subsumption_counter_model(&*concept("Mother"), &*concept("Human")).is_none());
+        assert!(reasoner.cache.borrow().is_empty());
+
+        // Told disjointness still needs the satisfiability of the subsumee
+        assert!(!reasoner.is_subsumed(&*concept("Mother"), &*concept("Male")));
+        assert!(reasoner.is_subsumed(&*concept("A"), &*concept("Female")));
+        assert!(!reasoner.is_subsumed(&*concept("Human"), &*concept("Mother")));
+        assert!(reasoner.find_subsumption_counter_model(&*concept("Mother"), &*concept("Male")).is_some());
+        assert!(reasoner.find_subsumption_counter_model(&*concept("A"), &*concept("Female")).is_none());
+    }
+
+    #[test]
+    fn test_satisfiability() {
+        let tbox = parse_tbox("A -> not B").unwrap();
+        let reasoner = Reasoner::new(KnowledgeBase::from_tbox(tbox)).unwrap();
+
+        assert!(reasoner.is_satisfiable(&*parse_concept("some r A").unwrap()));
+        assert!(!reasoner.is_satisfiable(&*parse_concept("and (A B)").unwrap()));
+        assert!(!reasoner.is_satisfiable(&*parse_concept("and ((some r A) (only r B))").unwrap()));
+        assert!(!reasoner.is_satisfiable(&*parse_concept("some r (and (C (not C)))").unwrap()));
+    }
+
+    #[test]
+    fn test_backjumping() {
+        let run = |concept: &str| run_with_statistics(&format!("({})[a]", concept), "", ExpansionStrategy::default());
+
+        // The clash does not depend on any of the disjunctions, so we should not try their other branches
+        // (the successor is needed, otherwise the complementary concepts clash before any branching)
+        let disjunctions = (0..12).map(|i| format!("(or (A{} B{}))", i, i)).collect::<Vec<String>>().join(" ");
+        let (abox, statistics) = run(&format!("and ({} (some r (and (C D))) (only r (not C)))", disjunctions));
+        assert!(abox.is_none());
+        assert_eq!(statistics.num_branch_points, 12);
+        assert!(statistics.num_steps < 50);
+
+        // Jumping back over the irrelevant choices should not lose the models
+        assert!(run("and ((or (C A)) (or (B1 D1)) (or (B2 D2)) (or ((not C) E)) (not E))").0.is_some());
+        assert!(run("and ((or (C A)) (or (B1 D1)) (or (B2 D2)) (or ((not C) E)) (not E) (not A))").0.is_none());
+        assert!(run("and ((or (A B)) (or (C D)) (or ((not A) (not C))) (not B) (not D))").0.is_none());
+        assert!(run("and ((or (A B)) (or (C D)) (or ((not A) (not C))) (not B))").0.is_some());
+    }
 
-    new_abox
+    #[test]
+    fn test_semantic_branching() {
+        let run = |concept: &str| run_with_statistics(&format!("({})[a]", concept), "", ExpansionStrategy::default());
+
+        // Each disjunction has a single disjunct left, so there is nothing to branch on
+        let (abox, statistics) = run("and ((not A) (or (A B)) (or ((not B) C)) (or ((not C) D)) (or ((not D) (not E) F)))");
+        assert!(abox.is_some());
+        assert_eq!(statistics.num_branch_points, 1);
+        assert_eq!(statistics.num_propagations, 3);
+        assert_eq!(statistics.num_saved_branches, 4);
+        assert!(run("and ((not A) (or (A B)) (or ((not B) C)) (not C))").0.is_none());
+
+        // The second disjunct is tried first, and then the first one gets its negation
+        let (abox, statistics) = run("and ((or (A (and (B C)))) (not C))");
+        let axioms = abox.unwrap().axioms.iter().map(|a| a.to_string()).collect::<Vec<String>>();
+        assert_eq!(statistics.num_branch_points, 1);
+        assert!(axioms.contains(&"(A)[a]".to_string()));
+        assert!(axioms.contains(&"(or (not B) (not C))[a]".to_string()));
+
+        // The reasoner sums the statistics up over its queries, the second one is answered from the cache
+        let reasoner = Reasoner::new(KnowledgeBase::parse("", "").unwrap()).unwrap();
+        let concept = parse_concept("and ((not A) (or (A B)) (or ((not B) C)) (or ((not C) D)) (or ((not D) (not E) F)))").unwrap();
+        assert!(reasoner.is_satisfiable(&*concept));
+        assert!(reasoner.find_concept_model(&*concept).is_some());
+        assert!(reasoner.is_satisfiable(&*concept));
+        let statistics = reasoner.statistics();
+        assert_eq!((statistics.num_runs, statistics.num_cache_hits), (2, 1));
+        assert_eq!((statistics.num_propagations, statistics.num_saved_branches), (6, 8));
+    }
+
+    #[test]
+    fn test_expansion_strategies() {
+        let run = |abox: &str, strategy: ExpansionStrategy| run_with_statistics(abox, "", strategy);
+        let deterministic_first = ExpansionStrategy { deterministic_first: true, ..Default::default() };
+        let smallest_disjunction_first = ExpansionStrategy { smallest_disjunction_first: true, ..Default::default() };
+
+        // The "only"-rule finds the clash before any branching
+        let abox = "(and ((or (A1 B1)) (or (A2 B2)) (only r (not C))))[a]\nr[a, b]\nC[b]";
+        assert_eq!(run(abox, ExpansionStrategy::default()).1.num_branch_points, 2);
+        assert_eq!(run(abox, deterministic_first).1.num_branch_points, 0);
+
+        // Either disjunct of the smaller disjunction leaves a single disjunct (A1) of the bigger one, so it is never branched on
+        let abox = "(and ((or (A1 A2 A3)) (or (B C)) (or ((not B) A1)) (or ((not C) A1))))[a]";
+        assert_eq!(run(abox, ExpansionStrategy::default()).1.num_branch_points, 2);
+        assert_eq!(run(abox, smallest_disjunction_first).1.num_branch_points, 1);
+
+        // The expansion does not depend on the order of the hash sets, so the runs are the same
+        let abox = "(and ((or (A B)) (or (C D)) (or (E F)) (some r (or (A G))) (only r (not A))))[a]\n(or (A C))[b]\nr[a, b]";
+        let models = (0..5).map(|_| {
+            let (model, statistics) = run(abox, ExpansionStrategy::default());
+            let mut axioms = model.unwrap().axioms.iter().map(|a| a.to_string()).collect::<Vec<String>>();
+            axioms.sort();
+            (axioms, statistics.num_steps)
+        }).collect::<Vec<_>>();
+        assert!(models.iter().all(|m| m == &models[0]));
+
+        // The merges move the concepts and the edges in the same order as well
+        let abox = "(and ({o} (or (A1 A2 A3)) (or (B C)) (or ((not B) A1)) (or ((not C) A1)) (some r (or (D E)))))[b]\n\
+                    (and ({o} (or (A2 (not A1))) (only r (not D))))[c]";
+        let models = (0..5).map(|_| {
+            let (model, statistics) = run(abox, ExpansionStrategy::default());
+            let mut axioms = model.unwrap().axioms.iter().map(|a| a.to_string()).collect::<Vec<String>>();
+            axioms.sort();
+            (axioms, statistics)
+        }).collect::<Vec<_>>();
+        assert!(models.iter().all(|m| m == &models[0]));
+    }
+
+    #[test]
+    fn test_satisfiability_cache() {
+        let reasoner = Reasoner::new(KnowledgeBase::parse("", "A -> some r (and (B C))\nB -> some s D").unwrap()).unwrap();
+        let run = |concept: &str| {
+            let abox = reasoner.concept_abox(&*parse_concept(concept).unwrap());
+            tableau_consistency(abox, reasoner.super_concept.clone(), &reasoner.unfolding_rules,
+                                &reasoner.tbox.rbox, reasoner.strategy, true, &mut reasoner.cache.borrow_mut())
+        };
+
+        // The successors of A get the labels from the first run
+        let (is_satisfiable, statistics) = run("A");
+        assert!(is_satisfiable);
+        assert_eq!(statistics.num_cache_hits, 0);
+
+        let (is_satisfiable, statistics) = run("and (E A)");
+        assert!(is_satisfiable);
+        assert_eq!(statistics.num_cache_hits, 1);
+        assert_eq!(run("and (E A)").1.num_steps, 0);
+
+        // The successor of "some r (and (E (not E)))" is known to be unsatisfiable after the first check
+        assert!(!run("and (E (not E))").0);
+        let (is_satisfiable, statistics) = run("some s (some r (and ((not E) E)))");
+        assert!(!is_satisfiable);
+        assert_eq!(statistics.num_cache_hits, 1);
+
+        // With an inverse relation the successors are expanded, since they can change the label of A
+        let (is_satisfiable, statistics) = run("and (A (only r (only r- (not E))) E)");
+        assert!(!is_satisfiable);
+        assert_eq!(statistics.num_cache_hits, 0);
+        assert!(!reasoner.is_subsumed(&*parse_concept("A").unwrap(), &*parse_concept("some r D").unwrap()));
+        assert!(reasoner.is_subsumed(&*parse_concept("A").unwrap(), &*parse_concept("some r (some s D)").unwrap()));
+
+        // The nominals can refer to the individuals of the knowledge base, so their labels do not get into the cache
+        let reasoner = Reasoner::new(KnowledgeBase::parse("A[joe]", "A -> not {joe}").unwrap()).unwrap();
+        assert!(!reasoner.is_consistent());
+        assert!(reasoner.is_satisfiable(&*parse_concept("A").unwrap()));
+        assert!(reasoner.cache.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_model_merging() {
+        let reasoner = Reasoner::new(KnowledgeBase::parse("", "A -> and (B (some r C))\nD -> only s E\nF -> only r (not C)").unwrap()).unwrap();
+        let concept = |s: &str| parse_concept(s).unwrap();
+        let model = |s: &str| reasoner.find_pseudo_model(&*concept(s)).unwrap();
+        let rbox = &reasoner.tbox.rbox;
+
+        assert!(model("A").can_merge(&model("not D"), rbox));
+        assert!(model("A").can_merge(&model("D"), rbox));
+        assert!(!model("A").can_merge(&model("not B"), rbox));
+        assert!(!model("A").can_merge(&model("F"), rbox));
+        assert!(reasoner.find_pseudo_model(&*concept("and (A (not B))")).is_none());
+
+        // The merging proves only the non-subsumptions, the rest goes to the tableau
+        assert!(!reasoner.is_subsumed(&*concept("A"), &*concept("D")));
+        assert!(reasoner.is_subsumed(&*concept("A"), &*concept("some r C")));
+        assert!(reasoner.is_subsumed(&*concept("and (A F)"), &*concept("E")));
+
+        // Outside of the classification the pseudo-models are not built just to be merged
+        let reasoner = Reasoner::new(KnowledgeBase::parse("", "A -> and (B (some r C))\nD -> only s E").unwrap()).unwrap();
+        assert!(!reasoner.is_subsumed(&*concept("A"), &*concept("D")));
+        assert!(reasoner.cached_pseudo_model(&*concept("not D")).is_none());
+        reasoner.classify();
+        assert!(reasoner.cached_pseudo_model(&*concept("A")).is_some());
+        assert!(reasoner.cached_pseudo_model(&*concept("not D")).is_some());
+
+        // With inverse relations the models are not merged
+        let reasoner = Reasoner::new(KnowledgeBase::parse("", "A -> some r (only r- B)").unwrap()).unwrap();
+        assert!(reasoner.find_pseudo_model(&*concept("A")).is_none());
+        assert!(reasoner.is_subsumed(&*concept("A"), &*concept("B")));
+    }
+
+    #[test]
+    fn test_concept_model() {
+        let reasoner = Reasoner::new(KnowledgeBase::from_tbox(parse_tbox("Woman == and (Person Female)").unwrap())).unwrap();
+        let model = reasoner.find_concept_model(&*parse_concept("some hasChild Woman").unwrap()).unwrap();
+        let concepts = model.concept_axioms.iter().map(|a| a.to_string()).collect::<Vec<String>>();
+
+        assert_eq!(model.individuals.len(), 2);
+        assert_eq!(model.relation_axioms.len(), 1);
+        assert_eq!(model.relation_axioms[0].lhs.name, "a");
+        assert!(concepts.contains(&format!("(Female)[{}]", model.relation_axioms[0].rhs)));
+
+        // A nominal {a} should not refer to the root, so it gets another name
+        let model = reasoner.find_concept_model(&*parse_concept("and ((not {a}) (some hasChild {a1}))").unwrap()).unwrap();
+        assert_eq!(model.relation_axioms[0].lhs.name, "a2");
+        assert_eq!(model.relation_axioms[0].rhs.name, "a1");
+    }
+
+    #[test]
+    fn test_inverse_relations() {
+        let reasoner = Reasoner::new(KnowledgeBase::from_tbox(parse_tbox("").unwrap())).unwrap();
+
+        assert!(reasoner.is_subsumed(&*parse_concept("A").unwrap(), &*parse_concept("only r (some r- A)").unwrap()));
+        assert!(reasoner.is_subsumed(&*parse_concept("some r (only r- B)").unwrap(), &*parse_concept("B").unwrap()));
+        assert!(!reasoner.is_subsumed(&*parse_concept("some r (only r B)").unwrap(), &*parse_concept("B").unwrap()));
+        assert!(!reasoner.is_satisfiable(&*parse_concept("and (A (some r (only r- (not A))))").unwrap()));
+
+        let abox = "hasChild-[ann, joe]\n(only hasChild Happy)[joe]\n(not Happy)[ann]";
+        assert!(!Reasoner::new(KnowledgeBase::parse(abox, "").unwrap()).unwrap().is_consistent());
+    }
+
+    #[test]
+    fn test_pairwise_blocking() {
+        // Each individual has an r-successor and at most one r-predecessor, so the model is an infinite chain
+        let tbox = parse_tbox("__TOP__ -> and ((some r A) (<= 1 r- __TOP__))").unwrap();
+        let reasoner = Reasoner::new(KnowledgeBase::from_tbox(tbox)).unwrap();
+
+        assert!(reasoner.is_satisfiable(&*parse_concept("A").unwrap()));
+        assert!(!reasoner.is_satisfiable(&*parse_concept("and (A (some r- (only r (not A))))").unwrap()));
+
+        // Every individual of a deep chain is checked for blocking on each step, which should not blow up with the depth
+        let concept = (0..30).fold("B".to_string(), |c, i| format!("and (B{} (some r ({})))", i, c));
+        assert!(reasoner.is_satisfiable(&*parse_concept(&concept).unwrap()));
+        assert!(!reasoner.is_satisfiable(&*parse_concept(&format!("and ((only r (only r (not B27))) ({}))", concept)).unwrap()));
+    }
+
+    #[test]
+    fn test_relation_hierarchy() {
+        let tbox = "role hasSon -> hasChild\nrole hasDaughter -> hasChild\nrole hasChild -> hasDescendant\nrole isChildOf == hasChild-";
+        let reasoner = Reasoner::new(KnowledgeBase::from_tbox(parse_tbox(tbox).unwrap())).unwrap();
+
+        assert!(is_subsumed(&reasoner, "some hasSon A", "some hasDescendant A"));
+        assert!(!is_subsumed(&reasoner, "some hasDescendant A", "some hasSon A"));
+        assert!(is_subsumed(&reasoner, "only hasChild A", "only hasSon A"));
+        assert!(is_subsumed(&reasoner, "A", "only hasChild (some isChildOf A)"));
+        assert!(is_subsumed(&reasoner, "and ((<= 1 hasChild __TOP__) (some hasSon A))", "only hasDaughter A"));
+    }
+
+    #[test]
+    fn test_transitive_relations() {
+        let tbox = "transitive partOf\nrole hasChild -> hasDescendant\ntransitive hasDescendant\nA -> some partOf A";
+        let reasoner = Reasoner::new(KnowledgeBase::from_tbox(parse_tbox(tbox).unwrap())).unwrap();
+
+        assert!(is_subsumed(&reasoner, "some partOf (some partOf B)", "some partOf B"));
+        assert!(is_subsumed(&reasoner, "some partOf- (some partOf- B)", "some partOf- B"));
+        assert!(is_subsumed(&reasoner, "only partOf B", "only partOf (only partOf B)"));
+        assert!(is_subsumed(&reasoner, "some hasChild (some hasChild B)", "some hasDescendant B"));
+        assert!(!is_subsumed(&reasoner, "some hasDescendant (some hasDescendant B)", "some hasChild B"));
+        assert!(!is_subsumed(&reasoner, "A", "B"));
+        assert!(!reasoner.is_satisfiable(&*parse_concept("and (A (only partOf (not A)))").unwrap()));
+
+        // Number restrictions are allowed only on the simple relations
+        let non_simple = |abox: &str, tbox: &str| match Reasoner::new(KnowledgeBase::parse(abox, tbox).unwrap()) {
+            Err(KnowledgeBaseError::NonSimpleRelation(e)) => Some((e.relation, e.concept)),
+            _ => None
+        };
+        let relations = "transitive partOf\nrole hasChild -> hasDescendant\ntransitive hasDescendant\n";
+        assert_eq!(non_simple("", &format!("{}A -> >= 2 partOf B", relations)), Some(("partOf".to_string(), ">= 2 partOf (B)".to_string())));
+        assert!(non_simple("", &format!("{}A -> not (some r (<= 1 hasDescendant- B))", relations)).is_some());
+        assert!(non_simple("(<= 3 hasDescendant __TOP__)[joe]", relations).is_some());
+        assert!(non_simple("", &format!("{}functional partOf", relations)).is_some());
+        assert!(non_simple("(<= 3 hasChild __TOP__)[joe]", &format!("{}A -> <= 1 hasChild B", relations)).is_none());
+    }
+
+    #[test]
+    fn test_nominals() {
+        let tbox = "Color == oneOf (red green blue)\nJoesFriend == some friendOf {joe}";
+
+        assert!(is_consistent("Color[c]\n(not {red})[c]\n(not {green})[c]", tbox));
+        assert!(!is_consistent("Color[c]\n(not {red})[c]\n(not {green})[c]\n(not {blue})[c]", tbox));
+        assert!(!is_consistent("Color[red]\n(not Color)[red]", tbox));
+        assert!(is_consistent("JoesFriend[ann]\nJoesFriend[bob]\n(<= 1 friendOf- __TOP__)[joe]", tbox));
+        assert!(!is_consistent("JoesFriend[ann]\nJoesFriend[bob]\n(<= 1 friendOf- __TOP__)[joe]\nHappy[ann]\n(not Happy)[bob]", tbox));
+
+        let reasoner = Reasoner::new(KnowledgeBase::from_tbox(parse_tbox(tbox).unwrap())).unwrap();
+        assert!(!reasoner.is_satisfiable(&*parse_concept("and ((some r (and ({joe} A))) (some s (and ({joe} (not A)))))").unwrap()));
+        assert!(reasoner.is_subsumed(&*parse_concept("{red}").unwrap(), &*parse_concept("Color").unwrap()));
+
+        // The concept queries are answered for an individual, which none of the nominals refers to
+        let reasoner = Reasoner::new(KnowledgeBase::from_tbox(parse_tbox("").unwrap())).unwrap();
+        assert!(reasoner.is_satisfiable(&*parse_concept("not {a}").unwrap()));
+        assert!(reasoner.is_satisfiable(&*parse_concept("and (B (not {a}))").unwrap()));
+        assert!(!reasoner.is_subsumed(&*parse_concept("B").unwrap(), &*parse_concept("{a}").unwrap()));
+
+        let reasoner = Reasoner::new(KnowledgeBase::from_tbox(parse_tbox("A -> not {a}").unwrap())).unwrap();
+        assert!(reasoner.is_satisfiable(&*parse_concept("A").unwrap()));
+        assert!(!reasoner.is_satisfiable(&*parse_concept("and (A {a})").unwrap()));
+        assert!(reasoner.is_subsumed(&*parse_concept("{a}").unwrap(), &*parse_concept("not A").unwrap()));
+    }
+
+    #[test]
+    fn test_functional_relations() {
+        let tbox = "functional hasMother\ninverseFunctional hasSSN";
+
+        assert!(is_consistent("hasMother[ann, m1]\nhasMother[ann, m2]\nHappy[m1]", tbox));
+        assert!(!is_consistent("hasMother[ann, m1]\nhasMother[ann, m2]\nHappy[m1]\n(not Happy)[m2]", tbox));
+        assert!(!is_consistent("hasSSN[a, n]\nhasSSN[b, n]\nA[a]\n(not A)[b]", tbox));
+
+        let reasoner = Reasoner::new(KnowledgeBase::from_tbox(parse_tbox("").unwrap())).unwrap();
+        assert!(reasoner.is_subsumed(&*parse_concept("= 2 r A").unwrap(), &*parse_concept(">= 2 r __TOP__").unwrap()));
+        assert!(!reasoner.is_satisfiable(&*parse_concept("and ((= 1 r __TOP__) (some r A) (some r (not A)))").unwrap()));
+    }
+
+    #[test]
+    fn test_individual_equality() {
+        let is_consistent = |abox: &str, una: bool| Reasoner::new(KnowledgeBase::parse(abox, "").unwrap()).unwrap()
+            .with_unique_name_assumption(una)
+            .is_consistent();
+
+        assert!(is_consistent("sameAs[ann, anna]\nHappy[ann]", false));
+        assert!(!is_consistent("sameAs[ann, anna]\nHappy[ann]\n(not Happy)[anna]", false));
+        assert!(!is_consistent("sameAs[a, b]\nsameAs[b, c]\ndifferentFrom[a, c]", false));
+        assert!(!is_consistent("differentFrom[a, a]", false));
+        assert!(!is_consistent("(<= 1 hasChild __TOP__)[joe]\nhasChild[joe, ann]\nhasChild[joe, bob]\ndifferentFrom[ann, bob]", false));
+
+        // Without UNA ann and bob can be the same child, with UNA they cannot
+        let abox = "(<= 1 hasChild __TOP__)[joe]\nhasChild[joe, ann]\nhasChild[joe, bob]";
+        assert!(is_consistent(abox, false));
+        assert!(!is_consistent(abox, true));
+        assert!(!is_consistent("sameAs[ann, anna]", true));
+        assert!(!is_consistent("({bob})[ann]", true));
+    }
+
+    #[test]
+    fn test_negative_relations() {
+        let tbox = "role hasSon -> hasChild\ntransitive hasDescendant";
+
+        assert!(is_consistent("not hasChild[joe, ann]\nhasChild[joe, bob]", tbox));
+        assert!(!is_consistent("not hasChild[joe, ann]\nhasChild[joe, ann]", tbox));
+        assert!(!is_consistent("not hasChild[joe, ann]\nhasChild-[ann, joe]", tbox));
+        assert!(!is_consistent("not hasChild[joe, ann]\nhasSon[joe, ann]", tbox));
+        assert!(!is_consistent("not hasDescendant[joe, ann]\nhasDescendant[joe, bob]\nhasDescendant[bob, ann]", tbox));
+
+        // The edges appear only after ann is merged with the child of joe
+        assert!(!is_consistent("not hasChild[joe, ann]\n(some hasChild {ann})[joe]", tbox));
+        assert!(!is_consistent("not hasChild[joe, ann]\nhasChild[joe, bob]\nsameAs[bob, ann]", tbox));
+    }
+
+    #[test]
+    fn test_initial_clash() {
+        assert!(!Reasoner::new(KnowledgeBase::parse("A[x]\n(not A)[x]", "").unwrap()).unwrap().is_consistent());
+    }
+
+    #[test]
+    fn test_lazy_unfolding() {
+        // Substituting these definitions into each other would double the concept on every level
+        let tbox = (0..25)
+            .map(|i| format!("A{} == and ((some r A{}) (only r A{}))", i, i + 1, i + 1))
+            .chain(vec!["A25 == and (B (not B))".to_string(), "C == and (D E)".to_string()])
+            .collect::<Vec<String>>()
+            .join("\n");
+        let reasoner = Reasoner::new(KnowledgeBase::from_tbox(parse_tbox(&tbox).unwrap())).unwrap();
+
+        assert!(!reasoner.is_satisfiable(&*parse_concept("A0").unwrap()));
+        assert!(reasoner.is_satisfiable(&*parse_concept("not A0").unwrap()));
+
+        // The negated definitions are unfolded as well
+        assert!(!reasoner.is_satisfiable(&*parse_concept("and ((not C) D E)").unwrap()));
+        assert!(reasoner.is_subsumed(&*parse_concept("and (D E)").unwrap(), &*parse_concept("C").unwrap()));
+    }
+
+    #[test]
+    fn test_absorption() {
+        let tbox = parse_tbox("A -> some r B\nand (C D) -> E\nF == or (X Y)\nand (F (some r G)) -> H\nsome r B -> C").unwrap();
+        let reasoner = Reasoner::new(KnowledgeBase::from_tbox(tbox)).unwrap();
+
+        assert!(reasoner.is_subsumed(&*parse_concept("and (A D)").unwrap(), &*parse_concept("E").unwrap()));
+        assert!(reasoner.is_subsumed(&*parse_concept("and (X (some r G))").unwrap(), &*parse_concept("H").unwrap()));
+        assert!(!reasoner.is_subsumed(&*parse_concept("C").unwrap(), &*parse_concept("E").unwrap()));
+        assert!(!reasoner.is_satisfiable(&*parse_concept("and (A (not E) D)").unwrap()));
+    }
+
+    #[test]
+    fn test_cyclic_definitions() {
+        // Each node has a next one, so the model is an infinite chain (which is cut by the blocking)
+        let tbox = parse_tbox("Node == and (Item (some next Node))\nEmpty == and (Item (only next Empty))").unwrap();
+        let error = Reasoner::new(KnowledgeBase::from_tbox(tbox.clone())).unwrap_err();
+        assert_eq!(error, KnowledgeBaseError::CyclicDefinition(CyclicDefinitionError { cycle: vec!["Empty".to_string(), "Empty".to_string()] }));
+
+        let reasoner = Reasoner::new(KnowledgeBase::from_tbox(tbox).with_cyclic_definitions(true)).unwrap();
+
+        assert!(reasoner.is_satisfiable(&*parse_concept("Node").unwrap()));
+        assert!(reasoner.is_satisfiable(&*parse_concept("and (Node Empty)").unwrap()));
+        assert!(!reasoner.is_satisfiable(&*parse_concept("and (Node (only next (not Node)))").unwrap()));
+        assert!(reasoner.is_subsumed(&*parse_concept("and (Item (some next Node))").unwrap(), &*parse_concept("Node").unwrap()));
+        assert!(!reasoner.is_subsumed(&*parse_concept("Item").unwrap(), &*parse_concept("Empty").unwrap()));
+    }
+
+    #[test]
+    fn test_top_and_bottom() {
+        let tbox = parse_tbox("Impossible -> __BOTTOM__\nA -> __TOP__").unwrap();
+        let reasoner = Reasoner::new(KnowledgeBase::from_tbox(tbox.clone())).unwrap();
+
+        assert!(!reasoner.is_satisfiable(&*parse_concept("__BOTTOM__").unwrap()));
+        assert!(!reasoner.is_satisfiable(&*parse_concept("some r Impossible").unwrap()));
+        assert!(!reasoner.is_satisfiable(&*parse_concept("not __TOP__").unwrap()));
+        assert!(reasoner.is_satisfiable(&*parse_concept("and (A (only r __BOTTOM__))").unwrap()));
+        assert!(reasoner.is_subsumed(&*parse_concept("__BOTTOM__").unwrap(), &*parse_concept("A").unwrap()));
+        assert!(reasoner.is_subsumed(&*parse_concept("A").unwrap(), &*parse_concept("__TOP__").unwrap()));
+        assert!(!Reasoner::new(KnowledgeBase::parse("__BOTTOM__[a]", "").unwrap()).unwrap().is_consistent());
+        assert!(!Reasoner::new(KnowledgeBase::new(parse_abox("Impossible[a]").unwrap(), tbox)).unwrap().is_consistent());
+        assert!(!Reasoner::new(KnowledgeBase::parse("A[a]", "__TOP__ -> __BOTTOM__").unwrap()).unwrap().is_consistent());
+    }
+
+    #[test]
+    fn test_instances_and_realization() {
+        let abox = "hasChild[joe, ann]\nhasChild[joe, bob]\nFemale[ann]\n(not Female)[bob]\nPerson[ann]\nPerson[bob]\nPerson[joe]";
+        let tbox = "Woman == and (Person Female)\nParent == and (Person (some hasChild Person))\nMother == and (Parent Female)";
+        let reasoner = Reasoner::new(KnowledgeBase::parse(abox, tbox).unwrap()).unwrap();
+        let names = |xs: Vec<Individual>| xs.into_iter().map(|x| x.name).collect::<Vec<String>>();
+
+        assert_eq!(names(reasoner.instances(&*parse_concept("Woman").unwrap())), vec!["ann"]);
+        assert_eq!(names(reasoner.instances(&*parse_concept("Person").unwrap())), vec!["ann", "bob", "joe"]);
+        assert_eq!(names(reasoner.instances(&*parse_concept("some hasChild (not Female)").unwrap())), vec!["joe"]);
+        assert!(names(reasoner.instances(&*parse_concept("Mother").unwrap())).is_empty());
+
+        let realization = reasoner.realize();
+        assert_eq!(realization[0].1, vec!["Woman"]);
+        assert_eq!(realization[1].1, vec!["Person"]);
+        assert_eq!(realization[2].1, vec!["Parent"]);
+    }
+
+    #[test]
+    fn test_instances_of_told_types() {
+        let abox = "Woman[ann]\nWoman[eva]\nWoman[kate]\nMan[bob]\nMan[joe]\n(not Female)[tom]\nPerson[sam]\nhasChild[sam, eva]";
+        let tbox = "Woman == and (Person Female)\nMan == and (Person (not Female))\nParent == and (Person (some hasChild Person))";
+        let reasoner = Reasoner::new(KnowledgeBase::parse(abox, tbox).unwrap()).unwrap();
+        let names = |xs: Vec<Individual>| xs.into_iter().map(|x| x.name).collect::<Vec<String>>();
+        let female = parse_concept("Female").unwrap();
+
+        reasoner.classify();
+        let num_runs = reasoner.statistics().num_runs;
+        let instances = reasoner.instances(&*female);
+        let runs = reasoner.statistics().num_runs - num_runs;
+
+        let num_runs = reasoner.statistics().num_runs;
+        reasoner.abox.individuals.iter().filter(|x| reasoner.is_instance(x, &*female)).count();
+        let naive_runs = reasoner.statistics().num_runs - num_runs;
+
+        // The told types decide the query for all the individuals but sam, so the tableau runs for the consistency,
+        // the types, which the taxonomy does not decide, and sam instead of a run for each of the 7 individuals
+        assert_eq!(names(instances), vec!["ann", "eva", "kate"]);
+        assert_eq!((runs, naive_runs), (5, 7));
+        assert_eq!(names(reasoner.instances(&*parse_concept("Parent").unwrap())), vec!["sam"]);
+    }
 }
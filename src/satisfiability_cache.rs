@@ -0,0 +1,185 @@
+/*
+    Satisfiability cache remembers the labels, which are known to be satisfiable or unsatisfiable
+    w.r.t. the TBox and the RBox of a reasoner. A label is the set of the interned concepts a node starts with,
+    e.g. a fresh successor gets the filler of its "some" concept and the fillers of the "only" concepts of its predecessor.
+    The cache lives as long as the reasoner does, so the concept factory is kept here between the tableau runs:
+    otherwise the same concepts would get different ids in different runs.
+    Satisfiable concepts (i.e. the labels of the roots of satisfiability checks) have their pseudo-models here as well.
+*/
+use std::collections::{HashSet, HashMap};
+
+use concept_factory::{ConceptFactory, ConceptId, ConceptNode};
+use pseudo_model::PseudoModel;
+
+
+// What makes the nodes of a tableau depend on each other (besides the "only" concepts)
+#[derive(Debug, Clone, Copy, Default)]
+struct ConceptFeatures {
+    has_nominals: bool,
+    has_inverses: bool,
+    has_at_most: bool
+}
+
+
+#[derive(Debug, Clone, Default)]
+pub struct SatisfiabilityCache {
+    // Graphs take the factory for a run and give it back afterwards
+    pub factory: ConceptFactory,
+    satisfiable: HashSet<Vec<ConceptId>>,
+    unsatisfiable: HashSet<Vec<ConceptId>>,
+    // None stands for a satisfiable concept, which model cannot be merged with the other ones
+    models: HashMap<Vec<ConceptId>, Option<PseudoModel>>,
+    features: HashMap<ConceptId, ConceptFeatures>,
+    // Satisfiable labels may be reused only in the runs, where the successors cannot affect their predecessors
+    pub reuse_satisfiable: bool
+}
+
+impl SatisfiabilityCache {
+    pub fn new() -> SatisfiabilityCache {
+        SatisfiabilityCache::default()
+    }
+
+    pub fn is_satisfiable(&self, label: &[ConceptId]) -> Option<bool> {
+        if self.unsatisfiable.contains(label) {
+            Some(false)
+        } else if self.satisfiable.contains(label) {
+            Some(true)
+        } else {
+            None
+        }
+    }
+
+    pub fn insert(&mut self, label: Vec<ConceptId>, is_satisfiable: bool) {
+        match is_satisfiable {
+            true => self.satisfiable.insert(label),
+            false => self.unsatisfiable.insert(label)
+        };
+    }
+
+    pub fn is_concept_satisfiable(&self, label: &[ConceptId]) -> Option<bool> {
+        // A label can be known to be satisfiable from the successors, but then it has no pseudo-model yet
+        if self.unsatisfiable.contains(label) {
+            Some(false)
+        } else if self.models.contains_key(label) {
+            Some(true)
+        } else {
+            None
+        }
+    }
+
+    pub fn insert_model(&mut self, label: Vec<ConceptId>, model: Option<PseudoModel>) {
+        self.satisfiable.insert(label.clone());
+        self.models.insert(label, model);
+    }
+
+    pub fn pseudo_model(&self, label: &[ConceptId]) -> Option<&PseudoModel> {
+        self.models.get(label).and_then(|model| model.as_ref())
+    }
+
+    pub fn len(&self) -> usize {
+        self.satisfiable.len() + self.unsatisfiable.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Checks that the concept has no nominals, "at-most" restrictions and inverse relations.
+    /// Without them nothing flows from a successor back to its predecessor (or to the other successors),
+    /// so a successor with a satisfiable label does not have to be expanded at all.
+    pub fn is_simple(&mut self, factory: &ConceptFactory, id: ConceptId) -> bool {
+        let features = self.features(factory, id);
+        !features.has_nominals && !features.has_inverses && !features.has_at_most
+    }
+
+    pub fn is_mergeable(&mut self, factory: &ConceptFactory, id: ConceptId) -> bool {
+        // The roots of two models can be merged, if nothing flows back from their successors to them
+        // and they cannot meet each other through the named individuals
+        let features = self.features(factory, id);
+        !features.has_nominals && !features.has_inverses
+    }
+
+    fn features(&mut self, factory: &ConceptFactory, id: ConceptId) -> ConceptFeatures {
+        if let Some(&features) = self.features.get(&id) {
+            return features;
+        }
+
+        let features = match factory.node(id) {
+            ConceptNode::Top | ConceptNode::Bottom | ConceptNode::Atomic(_) => ConceptFeatures::default(),
+            ConceptNode::Nominal(_) => ConceptFeatures { has_nominals: true, ..Default::default() },
+            ConceptNode::Not(c) => self.features(factory, *c),
+            ConceptNode::And(cs) | ConceptNode::Or(cs) => cs.iter().fold(ConceptFeatures::default(), |features, &c| {
+                let subfeatures = self.features(factory, c);
+
+                ConceptFeatures {
+                    has_nominals: features.has_nominals || subfeatures.has_nominals,
+                    has_inverses: features.has_inverses || subfeatures.has_inverses,
+                    has_at_most: features.has_at_most || subfeatures.has_at_most
+                }
+            }),
+            ConceptNode::Some(r, c) | ConceptNode::Only(r, c) | ConceptNode::AtLeast(_, r, c) | ConceptNode::AtMost(_, r, c) => {
+                let subfeatures = self.features(factory, *c);
+
+                ConceptFeatures {
+                    has_inverses: r.is_inverse || subfeatures.has_inverses,
+                    has_at_most: matches!(factory.node(id), ConceptNode::AtMost(..)) || subfeatures.has_at_most,
+                    ..subfeatures
+                }
+            }
+        };
+
+        self.features.insert(id, features);
+        features
+    }
+}
+
+
+pub fn label_key(factory: &ConceptFactory, concepts: &[ConceptId]) -> Vec<ConceptId> {
+    // Conjunctions are flattened and ⊤ is dropped, so that "and (A B)" and {A, B} are the same label
+    let mut key = vec![];
+    let mut stack = concepts.to_vec();
+
+    while let Some(c) = stack.pop() {
+        match factory.node(c) {
+            ConceptNode::Top => {},
+            ConceptNode::And(cs) => stack.extend(cs.iter().cloned()),
+            _ => key.push(c)
+        }
+    }
+
+    key.sort_unstable();
+    key.dedup();
+    key
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use concept::parse_concept;
+
+    #[test]
+    fn test_label_keys() {
+        let mut factory = ConceptFactory::new();
+        let mut intern = |s: &str| factory.intern(&*parse_concept(s).unwrap());
+        let (a, b, c, ab, abc, top) = (intern("A"), intern("B"), intern("some r C"), intern("and (A B)"),
+                                       intern("and ((and (B A)) (some r C))"), intern("⊤"));
+        let (only_inverse, at_most) = (intern("only r- A"), intern("and ((some r B) (<= 1 r A))"));
+        let mut cache = SatisfiabilityCache::new();
+
+        assert_eq!(label_key(&factory, &[ab, c, top]), label_key(&factory, &[abc]));
+        assert_eq!(label_key(&factory, &[b, a]), label_key(&factory, &[ab]));
+
+        cache.insert(label_key(&factory, &[ab]), true);
+        cache.insert(label_key(&factory, &[abc, a]), false);
+        assert_eq!(cache.is_satisfiable(&label_key(&factory, &[a, b])), Some(true));
+        assert_eq!(cache.is_satisfiable(&label_key(&factory, &[a, b, c])), Some(false));
+        assert_eq!(cache.is_satisfiable(&label_key(&factory, &[a, c])), None);
+
+        assert!(cache.is_simple(&factory, abc));
+        assert!(!cache.is_simple(&factory, only_inverse));
+        assert!(!cache.is_simple(&factory, at_most));
+        assert!(cache.is_mergeable(&factory, at_most));
+        assert!(!cache.is_mergeable(&factory, only_inverse));
+    }
+}
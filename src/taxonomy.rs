@@ -0,0 +1,390 @@
+/*
+    Taxonomy is the hierarchy of the named concepts of a TBox.
+    It is built with the enhanced traversal algorithm (Baader et al., 1994):
+    each concept is inserted by searching for its most specific subsumers top-down
+    and for its most general subsumees bottom-up. The part of the hierarchy, which is already built,
+    lets us skip the tests, which results are implied (e.g. D cannot subsume C if a parent of D does not).
+    The told subsumers of C are above it without any test, and the bottom search is skipped for the names,
+    which nothing satisfiable can be below.
+*/
+use std::fmt;
+use std::collections::{HashSet, HashMap};
+
+use concept::{Concept, AtomicConcept, TopConcept, BottomConcept};
+use reasoner::Reasoner;
+
+
+const TOP: usize = 0;
+const BOTTOM: usize = 1;
+
+
+#[derive(Debug, Clone)]
+pub struct TaxonomyNode {
+    // All the concepts of a node are equivalent
+    pub concepts: Vec<String>,
+    pub parents: HashSet<usize>,
+    pub children: HashSet<usize>
+}
+
+#[derive(Debug, Clone)]
+pub struct Taxonomy {
+    nodes: Vec<TaxonomyNode>,
+    node_of: HashMap<String, usize>,
+    // Only the tests, which have run the tableau: told subsumers, cache hits and merged pseudo-models are free
+    pub num_subsumption_tests: usize
+}
+
+impl Taxonomy {
+    pub fn build(reasoner: &Reasoner, concept_names: &[String], names_without_subsumees: &HashSet<String>) -> Taxonomy {
+        debug!("Classifying {} concepts...", concept_names.len());
+        let num_runs = reasoner.statistics().num_runs;
+        let mut taxonomy = Taxonomy::new();
+
+        for name in concept_names {
+            taxonomy.insert(reasoner, name, !names_without_subsumees.contains(name));
+        }
+
+        taxonomy.num_subsumption_tests = reasoner.statistics().num_runs - num_runs;
+        taxonomy
+    }
+
+    fn new() -> Taxonomy {
+        let top = TaxonomyNode { concepts: vec![], parents: HashSet::new(), children: vec![BOTTOM].into_iter().collect() };
+        let bottom = TaxonomyNode { concepts: vec![], parents: vec![TOP].into_iter().collect(), children: HashSet::new() };
+
+        Taxonomy { nodes: vec![top, bottom], node_of: HashMap::new(), num_subsumption_tests: 0 }
+    }
+
+    fn insert(&mut self, reasoner: &Reasoner, name: &str, has_subsumees: bool) {
+        if self.node_of.contains_key(name) {
+            return;
+        }
+
+        let concept = AtomicConcept { name: name.to_string() };
+
+        if !reasoner.is_satisfiable(&concept) {
+            debug!("{} is unsatisfiable", name);
+            return self.add_to_node(BOTTOM, name);
+        }
+
+        // The pseudo-models of C and ¬C are cached now, so the tests against C can merge them instead of running the tableau
+        reasoner.is_satisfiable(&*concept.negate());
+
+        let parents = self.top_search(reasoner, &concept);
+
+        // Nothing but ⊥ is below C, so it is neither equivalent to its parent nor above any other node
+        if !has_subsumees {
+            debug!("Nothing satisfiable is subsumed by {}", name);
+            return self.add_node(parents, vec![BOTTOM], name);
+        }
+
+        // C is equivalent to its parent P iff P ⊑ C (and then P is the only parent)
+        if parents.len() == 1 && reasoner.is_subsumed(&*self.node_concept(parents[0]), &concept) {
+            debug!("{} is equivalent to {}", name, self.node_concept(parents[0]));
+            return self.add_to_node(parents[0], name);
+        }
+
+        let children = self.bottom_search(reasoner, &concept, &parents);
+        self.add_node(parents, children, name);
+    }
+
+    fn add_node(&mut self, parents: Vec<usize>, children: Vec<usize>, name: &str) {
+        let node = self.nodes.len();
+
+        for &p in &parents {
+            for &c in &children {
+                self.nodes[p].children.remove(&c);
+                self.nodes[c].parents.remove(&p);
+            }
+
+            self.nodes[p].children.insert(node);
+        }
+
+        for &c in &children {
+            self.nodes[c].parents.insert(node);
+        }
+
+        self.nodes.push(TaxonomyNode {
+            concepts: vec![],
+            parents: parents.into_iter().collect(),
+            children: children.into_iter().collect()
+        });
+        self.add_to_node(node, name);
+    }
+
+    fn add_to_node(&mut self, node: usize, name: &str) {
+        // The names of a node are kept sorted, so they do not depend on the order of the insertion
+        let concepts = &mut self.nodes[node].concepts;
+        let position = concepts.binary_search_by(|c| c.as_str().cmp(name)).unwrap_or_else(|p| p);
+        concepts.insert(position, name.to_string());
+        self.node_of.insert(name.to_string(), node);
+    }
+
+    fn top_search(&self, reasoner: &Reasoner, concept: &AtomicConcept) -> Vec<usize> {
+        // The told subsumers of the concept and everything above them are known to subsume it
+        let told_subsumers = self.nodes.iter()
+            .enumerate()
+            .filter(|(_, n)| n.concepts.first().is_some_and(|name| reasoner.told_subsumers().is_subsumed(&concept.name, name)))
+            .flat_map(|(node, _)| self.ancestors(node))
+            .map(|node| (node, true))
+            .collect();
+
+        self.search_down(told_subsumers, |node| reasoner.is_subsumed(concept, &*self.node_concept(node)))
+    }
+
+    /// Finds the most specific nodes, which are above something (e.g. subsume a concept).
+    /// We go down from the top, but only through the nodes, which are above it.
+    /// The nodes, which are known to be above it (or not), are not tested.
+    fn search_down<F>(&self, mut results: HashMap<usize, bool>, mut is_above: F) -> Vec<usize> where F: FnMut(usize) -> bool {
+        let mut visited = HashSet::new();
+        let mut stack = vec![TOP];
+        let mut nodes = vec![];
+
+        results.insert(TOP, true);
+
+        while let Some(node) = stack.pop() {
+            if !visited.insert(node) {
+                continue;
+            }
+
+            let candidates = self.nodes[node].children.iter().cloned().filter(|&c| c != BOTTOM).collect::<Vec<usize>>();
+            let children_above = candidates
+                .into_iter()
+                .filter(|&c| self.is_above(c, &mut is_above, &mut results))
+                .collect::<Vec<usize>>();
+
+            if children_above.is_empty() {
+                nodes.push(node);
+            } else {
+                stack.extend(children_above);
+            }
+        }
+
+        nodes.sort();
+        nodes
+    }
+
+    fn is_above<F>(&self, node: usize, is_above: &mut F, results: &mut HashMap<usize, bool>) -> bool where F: FnMut(usize) -> bool {
+        // A node can be above something only if all of its parents are
+        if let Some(&result) = results.get(&node) {
+            return result;
+        }
+
+        let result = self.nodes[node].parents.iter().all(|&p| self.is_above(p, is_above, results)) && is_above(node);
+
+        results.insert(node, result);
+        result
+    }
+
+    /// Finds the most general nodes, which are subsumed by the concept.
+    /// Such nodes are subsumed by all the parents of the concept as well,
+    /// so we do not even test the nodes, which are not below each of the parents.
+    fn bottom_search(&self, reasoner: &Reasoner, concept: &dyn Concept, parents: &[usize]) -> Vec<usize> {
+        let candidates = parents.iter()
+            .map(|&p| self.descendants(p))
+            .fold(None, |acc: Option<HashSet<usize>>, ds| Some(match acc {
+                Some(acc) => acc.intersection(&ds).cloned().collect(),
+                None => ds
+            }))
+            .unwrap_or_default();
+        let mut is_subsumed = HashMap::new();
+        let mut visited = HashSet::new();
+        let mut stack = vec![BOTTOM];
+        let mut children = vec![];
+
+        is_subsumed.insert(BOTTOM, true);
+
+        while let Some(node) = stack.pop() {
+            if !visited.insert(node) {
+                continue;
+            }
+
+            let candidate_parents = self.nodes[node].parents.iter().cloned().filter(|p| candidates.contains(p)).collect::<Vec<usize>>();
+            let subsumed_parents = candidate_parents
+                .into_iter()
+                .filter(|&p| self.is_subsumed_by(reasoner, p, concept, &candidates, &mut is_subsumed))
+                .collect::<Vec<usize>>();
+
+            if subsumed_parents.is_empty() {
+                children.push(node);
+            } else {
+                stack.extend(subsumed_parents);
+            }
+        }
+
+        children.sort();
+        children
+    }
+
+    fn is_subsumed_by(&self, reasoner: &Reasoner, node: usize, concept: &dyn Concept,
+                      candidates: &HashSet<usize>, results: &mut HashMap<usize, bool>) -> bool {
+        // A node can be subsumed by the concept only if all of its children are
+        if let Some(&result) = results.get(&node) {
+            return result;
+        }
+
+        let children = self.nodes[node].children.iter().cloned().collect::<Vec<usize>>();
+        let result = candidates.contains(&node)
+            && children.into_iter().all(|c| self.is_subsumed_by(reasoner, c, concept, candidates, results))
+            && reasoner.is_subsumed(&*self.node_concept(node), concept);
+
+        results.insert(node, result);
+        result
+    }
+
+    fn descendants(&self, node: usize) -> HashSet<usize> {
+        let mut descendants = HashSet::new();
+        let mut stack = vec![node];
+
+        while let Some(x) = stack.pop() {
+            if descendants.insert(x) {
+                stack.extend(self.nodes[x].children.iter().cloned());
+            }
+        }
+
+        descendants
+    }
+
+    fn ancestors(&self, node: usize) -> HashSet<usize> {
+        let mut ancestors = HashSet::new();
+        let mut stack = vec![node];
+
+        while let Some(x) = stack.pop() {
+            if ancestors.insert(x) {
+                stack.extend(self.nodes[x].parents.iter().cloned());
+            }
+        }
+
+        ancestors
+    }
+
+    fn node_concept(&self, node: usize) -> Box<dyn Concept> {
+        match node {
+            TOP => Box::new(TopConcept),
+            BOTTOM => Box::new(BottomConcept),
+            _ => Box::new(AtomicConcept { name: self.nodes[node].concepts[0].clone() })
+        }
+    }
+
+    fn node_names(&self, node: usize) -> Vec<String> {
+        let special_name = match node {
+            TOP => Some("__TOP__".to_string()),
+            BOTTOM => Some("__BOTTOM__".to_string()),
+            _ => None
+        };
+
+        special_name.into_iter().chain(self.nodes[node].concepts.iter().cloned()).collect()
+    }
+
+    fn collect_names(&self, nodes: &HashSet<usize>) -> Vec<String> {
+        let mut names = nodes.iter().flat_map(|&n| self.node_names(n)).collect::<Vec<String>>();
+        names.sort();
+        names
+    }
+
+    pub fn parents(&self, name: &str) -> Vec<String> {
+        // Direct subsumers of the concept (empty if the concept is unknown)
+        self.node_of.get(name).map_or(vec![], |&n| self.collect_names(&self.nodes[n].parents))
+    }
+
+    pub fn children(&self, name: &str) -> Vec<String> {
+        // Direct subsumees of the concept (empty if the concept is unknown)
+        self.node_of.get(name).map_or(vec![], |&n| self.collect_names(&self.nodes[n].children))
+    }
+
+    pub fn equivalents(&self, name: &str) -> Vec<String> {
+        // All the concepts equivalent to the given one (including itself)
+        self.node_of.get(name).map_or(vec![], |&n| self.node_names(n))
+    }
+
+    pub fn is_subsumed(&self, sub: &str, sup: &str) -> Option<bool> {
+        // Whether the hierarchy puts one concept below the other (None if any of them is unknown)
+        let (&sub_node, &sup_node) = (self.node_of.get(sub)?, self.node_of.get(sup)?);
+        Some(self.ancestors(sub_node).contains(&sup_node))
+    }
+
+    pub fn most_specific_concepts<F>(&self, mut is_instance: F) -> Vec<String> where F: FnMut(&str) -> bool {
+        // Realization of an individual: the most specific concepts it is an instance of.
+        // The hierarchy lets us skip the children of the concepts, which are not its types.
+        let nodes = self.search_down(HashMap::new(), |node| is_instance(&self.nodes[node].concepts[0]));
+        self.collect_names(&nodes.into_iter().collect())
+    }
+
+    pub fn unsatisfiable(&self) -> Vec<String> {
+        self.nodes[BOTTOM].concepts.clone()
+    }
+}
+
+impl fmt::Display for Taxonomy {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let format_names = |names: Vec<String>| match names.is_empty() {
+            true => "none".to_string(),
+            false => names.join(", ")
+        };
+        let nodes = (0..self.nodes.len())
+            .filter(|&n| n != BOTTOM)
+            .map(|n| {
+                let children = self.nodes[n].children.iter().cloned().filter(|&c| c != BOTTOM).collect();
+
+                format!("{} (parents: {}; children: {})", self.node_names(n).join(" == "),
+                    format_names(self.collect_names(&self.nodes[n].parents)), format_names(self.collect_names(&children)))
+            })
+            .collect::<Vec<String>>();
+
+        write!(fmt, "Taxonomy:\n  - {}\nUnsatisfiable concepts: {}", nodes.join("\n  - "), format_names(self.unsatisfiable()))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tbox::parse_tbox;
+    use knowledge_base::KnowledgeBase;
+
+    #[test]
+    fn test_classification() {
+        let tbox = parse_tbox("
+            Woman == and (Person Female)
+            Man == and (Person (not Female))
+            Mother == and (Woman (some hasChild Person))
+            Parent == and (Person (some hasChild Person))
+            Human == Person
+            Unicorn -> and (Horse (not Horse))
+        ").unwrap();
+        let taxonomy = Reasoner::new(KnowledgeBase::from_tbox(tbox)).unwrap().classify();
+
+        assert_eq!(taxonomy.equivalents("Person"), vec!["Human", "Person"]);
+        assert_eq!(taxonomy.parents("Human"), vec!["__TOP__"]);
+        assert_eq!(taxonomy.children("Person"), vec!["Man", "Parent", "Woman"]);
+        assert_eq!(taxonomy.parents("Mother"), vec!["Parent", "Woman"]);
+        assert_eq!(taxonomy.children("Mother"), vec!["Unicorn", "__BOTTOM__"]);
+        assert_eq!(taxonomy.parents("Horse"), vec!["__TOP__"]);
+        assert_eq!(taxonomy.unsatisfiable(), vec!["Unicorn"]);
+
+        // 9 concepts would require 72 subsumption tests with a brute force
+        assert!(taxonomy.num_subsumption_tests < 72);
+    }
+
+    #[test]
+    fn test_free_subsumption_tests() {
+        let tbox = parse_tbox("
+            A -> and (B (some r C))
+            B -> D
+            E == and (D (some r C))
+            F -> D
+        ").unwrap();
+        let mut names = tbox.names_without_subsumees().into_iter().collect::<Vec<String>>();
+        names.sort();
+        let taxonomy = Reasoner::new(KnowledgeBase::from_tbox(tbox)).unwrap().classify();
+
+        // A and F are not searched for subsumees, but A is still below E
+        assert_eq!(names, vec!["A", "F"]);
+        assert_eq!(taxonomy.parents("A"), vec!["B", "E"]);
+        assert_eq!(taxonomy.children("A"), vec!["__BOTTOM__"]);
+        assert_eq!(taxonomy.children("D"), vec!["B", "E", "F"]);
+
+        // Only the tests, which run the tableau, are counted (a brute force would take 30 of them)
+        assert!(taxonomy.num_subsumption_tests < 30);
+    }
+}
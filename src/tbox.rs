@@ -1,162 +1,399 @@
 use std::fmt;
 use std::hash;
-use std::collections::HashSet;
-use std::iter::FromIterator;
+use std::collections::{HashSet, HashMap};
 
-use abox::{ABox, ABoxAxiom, ABoxAxiomType, ConceptAxiom};
-use concept::{Concept, ConjunctionConcept, DisjunctionConcept, parse_concept};
+use concept::{Concept, ConceptType, AtomicConcept, TopConcept, ConjunctionConcept, DisjunctionConcept, AtMostConcept, Individual,
+              parse_concept_in, extract_atomic_names, extract_nominal_individuals};
+use error::{ParseError, CyclicDefinitionError, NonSimpleRelationError};
+use rbox::{RBox, RBOX_KEYWORDS, add_rbox_axiom};
 
 
-pub fn parse_tbox(tbox_str: &str) -> TBox {
+pub fn parse_tbox(tbox_str: &str) -> Result<TBox, Vec<ParseError>> {
+    // Parses the whole TBox, collecting errors from all the lines instead of stopping at the first one
     debug!("Parsing TBox!");
 
-    let tbox_str = tbox_str.trim();
     let mut tbox = TBox::new();
+    let mut errors = vec![];
 
-    for line in tbox_str.lines() {
+    for (i, line) in tbox_str.lines().enumerate() {
         debug!("Parsing line: {}", line);
+        let trimmed_line = line.trim();
 
-        if line.len() > 0 && !line.starts_with('#') {
-            tbox.axioms.insert(Box::new(parse_tbox_axiom(line)));
+        if trimmed_line.is_empty() || trimmed_line.starts_with('#') {
+            continue;
+        }
+
+        let first_word = trimmed_line.split_whitespace().next().unwrap();
+
+        if RBOX_KEYWORDS.contains(&first_word) {
+            if let Err(e) = add_rbox_axiom(&mut tbox.rbox, line) {
+                errors.push(e.at_line(i + 1));
+            }
+        } else {
+            match parse_tbox_axiom(line) {
+                Ok(axiom) => { tbox.axioms.insert(Box::new(axiom)); },
+                Err(e) => errors.push(e.at_line(i + 1))
+            }
         }
     }
 
-    tbox
+    if errors.is_empty() { Ok(tbox) } else { Err(errors) }
 }
 
 
-pub fn parse_tbox_axiom(tbox_line: &str) -> TBoxAxiom {
-    let tbox_line = tbox_line.trim();
-    let delimiter = if tbox_line.contains("==") { "==" } else { "->" };
-    let axiom_type = if delimiter == "==" { TBoxAxiomType::Definition } else { TBoxAxiomType::Inclusion };
-    let delimiter_idx = tbox_line.find(delimiter).unwrap();
+pub fn parse_tbox_axiom(tbox_line: &str) -> Result<TBoxAxiom, ParseError> {
+    let axiom_str = tbox_line.trim();
+    let (delimiter, axiom_type) = if axiom_str.contains("==") {
+        ("==", TBoxAxiomType::Definition)
+    } else if axiom_str.contains("->") {
+        ("->", TBoxAxiomType::Inclusion)
+    } else {
+        return Err(ParseError::new(tbox_line, axiom_str, "a definition `A == C` or an inclusion `C -> D`"));
+    };
+    let delimiter_idx = axiom_str.find(delimiter).unwrap();
+
+    Ok(TBoxAxiom {
+        axiom_type,
+        lhs: parse_concept_in(&axiom_str[..delimiter_idx], tbox_line)?.convert_to_nnf(),
+        rhs: parse_concept_in(&axiom_str[delimiter_idx + 2..], tbox_line)?.convert_to_nnf()
+    })
+}
 
-    TBoxAxiom {
-        axiom_type: axiom_type,
-        lhs: parse_concept(&tbox_line[..delimiter_idx]).convert_to_nnf(),
-        rhs: parse_concept(&tbox_line[delimiter_idx + 2..]).convert_to_nnf()
+pub fn parse_subsumption_queries(queries_str: &str) -> Result<Vec<TBoxAxiom>, Vec<ParseError>> {
+    // Every non-empty line is a query "C -> D", which should be checked w.r.t. some background TBox
+    let mut queries = vec![];
+    let mut errors = vec![];
+
+    for (i, line) in queries_str.lines().enumerate() {
+        let trimmed_line = line.trim();
+
+        if trimmed_line.is_empty() || trimmed_line.starts_with('#') {
+            continue;
+        }
+
+        match parse_tbox_axiom(line) {
+            Ok(ref axiom) if axiom.axiom_type == TBoxAxiomType::Definition => {
+                errors.push(ParseError::new(line, trimmed_line, "a subsumption query `C -> D`").at_line(i + 1));
+            },
+            Ok(axiom) => queries.push(axiom),
+            Err(e) => errors.push(e.at_line(i + 1))
+        }
     }
+
+    if errors.is_empty() { Ok(queries) } else { Err(errors) }
 }
 
+
 #[derive(Debug, Clone)]
 pub struct TBox {
-    axioms: HashSet<Box<TBoxAxiom>>
+    axioms: HashSet<Box<TBoxAxiom>>,
+    pub rbox: RBox
+}
+
+impl Default for TBox {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TBox {
     pub fn new() -> TBox {
-        TBox {axioms: HashSet::new()}
+        TBox {axioms: HashSet::new(), rbox: RBox::new()}
     }
 }
 
 impl TBox {
-    pub fn expand_all_definitions(&mut self) {
-        info!("Expanding TBox definitions...");
-        // Expands all the definitions in such a way that we do not use
-        // definitions inside definitions
-        let mut definitions = self.axioms.clone().into_iter()
-            .filter(|a| a.axiom_type == TBoxAxiomType::Definition)
-            .collect::<Vec<Box<TBoxAxiom>>>();
-        let mut definitions_updated = definitions.clone();
-        let mut applied_defs = HashSet::new();
-
-        while let Some(def) = definitions.pop() {
-            applied_defs.insert(def.lhs.clone());
-            // Expanding the definition in all the possible definitions
-            // After that we will not have this definition anywhere except for itself
-            definitions_updated = definitions_updated
-                .into_iter()
-                .clone()
-                .map(|d| {
-                    if def.lhs.to_string() == d.lhs.to_string() {
-                        Box::new(*d)
-                    } else {
-                        Box::new(TBoxAxiom {
-                            axiom_type: d.axiom_type.clone(),
-                            lhs: d.lhs.clone(),
-                            rhs: d.rhs.replace_concept(def.lhs.clone(), def.rhs.clone())
-                        })
-                    }
-                })
-                .collect::<Vec<Box<TBoxAxiom>>>();
-
-            definitions = definitions_updated.clone()
-                .into_iter()
-                .filter(|d| {!applied_defs.contains(&d.lhs)})
-                .collect();
+    pub fn concept_names(&self) -> Vec<String> {
+        // All the atomic concepts mentioned in the TBox, sorted to make classification deterministic
+        let mut names = self.axioms.iter()
+            .flat_map(|a| extract_atomic_names(&*a.lhs).into_iter().chain(extract_atomic_names(&*a.rhs)))
+            .collect::<HashSet<String>>()
+            .into_iter()
+            .collect::<Vec<String>>();
+
+        names.sort();
+        names
+    }
+
+    pub fn names_without_subsumees(&self) -> HashSet<String> {
+        // The names, which occur only as the left sides of the inclusions "A -> C": every model of the TBox
+        // stays a model if such a name becomes empty, so only the unsatisfiable concepts are subsumed by it
+        let mut lhs_names = HashSet::new();
+        let mut other_names = HashSet::new();
+
+        for axiom in &self.axioms {
+            match axiom.lhs.downcast_ref::<AtomicConcept>() {
+                Some(concept) if axiom.axiom_type == TBoxAxiomType::Inclusion => { lhs_names.insert(concept.name.clone()); },
+                _ => other_names.extend(extract_atomic_names(&*axiom.lhs))
+            }
+
+            other_names.extend(extract_atomic_names(&*axiom.rhs));
         }
+
+        lhs_names.difference(&other_names).cloned().collect()
     }
 
-    pub fn apply_definitions_to_abox(&self, abox: &mut ABox) {
-        info!("Applying expanded TBox definitions to an ABox...");
-        let definitions = self.axioms.clone().into_iter()
-            .filter(|a| a.axiom_type == TBoxAxiomType::Definition)
-            .collect::<Vec<Box<TBoxAxiom>>>();
-
-        abox.axioms = HashSet::from_iter(abox.axioms.clone().iter().map(|a| {
-            match a.axiom_type() {
-                ABoxAxiomType::Relation => a.clone(),
-                ABoxAxiomType::Concept => {
-                    let axiom = a.downcast_ref::<ConceptAxiom>().unwrap();
-                    let replaced_concept = definitions.clone()
-                        .iter()
-                        .fold(axiom.concept.clone(), |c, def| {
-                            c.replace_concept(def.lhs.clone(), def.rhs.clone())
-                        });
-
-                    Box::new(ConceptAxiom {
-                        concept: replaced_concept,
-                        individual: axiom.individual.clone()
-                    }) as Box<dyn ABoxAxiom>
-                },
+    pub fn nominal_individuals(&self) -> HashSet<Individual> {
+        // All the individuals, which the nominals of the TBox refer to
+        self.axioms.iter()
+            .flat_map(|a| extract_nominal_individuals(&*a.lhs).into_iter().chain(extract_nominal_individuals(&*a.rhs)))
+            .collect()
+    }
+
+    fn sorted_axioms(&self) -> Vec<&TBoxAxiom> {
+        // The order of the axioms decides the order of the expansion, so it should not depend on the hash set
+        let mut axioms = self.axioms.iter().map(|a| &**a).collect::<Vec<&TBoxAxiom>>();
+        axioms.sort_by_key(|a| a.to_string());
+        axioms
+    }
+
+    fn named_definitions(&self) -> HashMap<String, &TBoxAxiom> {
+        // Definitions "A == C" of the named concepts can be unfolded lazily.
+        // If A has several definitions, only one of them can (the others become GCIs),
+        // we take the smallest one to keep it deterministic
+        let mut definitions: HashMap<String, &TBoxAxiom> = HashMap::new();
+
+        for axiom in self.axioms.iter().filter(|a| a.axiom_type == TBoxAxiomType::Definition) {
+            if let Some(concept) = axiom.lhs.downcast_ref::<AtomicConcept>() {
+                let definition = definitions.entry(concept.name.clone()).or_insert(axiom);
+
+                if axiom.rhs.to_string() < definition.rhs.to_string() {
+                    *definition = axiom;
+                }
             }
-        }))
+        }
+
+        definitions
     }
 
-    pub fn apply_definitions_to_inclusions(&mut self) {
-        info!("Applying expanded TBox definitions to GCIs...");
-        let definitions = self.axioms.clone().into_iter()
-            .filter(|a| a.axiom_type == TBoxAxiomType::Definition)
-            .collect::<Vec<Box<TBoxAxiom>>>();
-        let mut inclusions = self.axioms.clone().into_iter()
-            .filter(|a| a.axiom_type == TBoxAxiomType::Inclusion)
-            .collect::<Vec<Box<TBoxAxiom>>>();
+    fn lazy_definitions(&self) -> HashMap<String, &TBoxAxiom> {
+        // Cyclic definitions cannot be unfolded: the unfolding would not terminate,
+        // besides "A == some r A" does not define A uniquely. So they are just pairs of GCIs
+        // (the GCI rule and the blocking take care of them)
+        let definitions = self.named_definitions();
+        let cyclic_names = definitions.keys()
+            .filter(|name| find_definition_path(name, name, &definitions, &mut HashSet::new()).is_some())
+            .cloned()
+            .collect::<HashSet<String>>();
+
+        definitions.into_iter().filter(|(name, _)| !cyclic_names.contains(name)).collect()
+    }
+
+    pub fn find_definition_cycle(&self) -> Option<Vec<String>> {
+        let definitions = self.named_definitions();
+        let mut names = definitions.keys().cloned().collect::<Vec<String>>();
+        names.sort();
+
+        names.into_iter().find_map(|name| {
+            let path = find_definition_path(&name, &name, &definitions, &mut HashSet::new())?;
+            Some(Some(name).into_iter().chain(path).collect())
+        })
+    }
+
+    pub fn check_definitions(&self) -> Result<(), CyclicDefinitionError> {
+        // Cyclic definitions are supported (as GCIs), but they are likely to be a mistake
+        match self.find_definition_cycle() {
+            Some(cycle) => Err(CyclicDefinitionError { cycle }),
+            None => Ok(())
+        }
+    }
+
+    pub fn check_number_restrictions(&self) -> Result<(), NonSimpleRelationError> {
+        // Functional relations are restricted as well (by "<= 1 r __TOP__")
+        let functional_restrictions = self.rbox.functional_relations()
+            .into_iter()
+            .map(|r| Box::new(AtMostConcept { amount: 1, relation: r, subconcept: Box::new(TopConcept) }) as Box<dyn Concept>)
+            .collect::<Vec<Box<dyn Concept>>>();
+
+        self.sorted_axioms()
+            .into_iter()
+            .flat_map(|a| vec![&*a.lhs, &*a.rhs])
+            .chain(functional_restrictions.iter().map(|c| &**c))
+            .try_for_each(|c| self.rbox.check_number_restrictions(c))
+    }
+
+    /// Prepares the TBox for the tableau: the definitions, which are not cyclic, become lazy unfolding rules,
+    /// as many GCIs as possible are absorbed into such rules, and the rest of them is aggregated into a single concept.
+    pub fn absorb(&self) -> Absorption {
+        debug!("Preparing lazy unfolding rules...");
+        let lazy_definitions = self.lazy_definitions();
+        let (absorbed, remaining) = self.absorb_inclusions(&lazy_definitions);
+        let (num_absorbed, num_inclusions) = (absorbed.len(), absorbed.len() + remaining.len());
+        debug!("Absorbed {} of {} GCIs into lazy unfolding rules", num_absorbed, num_inclusions);
 
-        for inclusion in &mut inclusions {
-            for def in &definitions {
-                inclusion.lhs = inclusion.lhs.replace_concept(def.lhs.clone(), def.rhs.clone());
-                inclusion.rhs = inclusion.rhs.replace_concept(def.lhs.clone(), def.rhs.clone());
+        Absorption {
+            unfolding_rules: definition_rules(&lazy_definitions).into_iter().chain(absorbed).collect(),
+            super_concept: self.aggregate_inclusions(remaining),
+            num_absorbed,
+            num_inclusions
+        }
+    }
+
+    /// Splits the GCIs into the absorbed ones (as unfolding rules) and the remaining ones,
+    /// which have to be added to every individual.
+    /// The definitions, which are not unfolded lazily, are just a pair of GCIs.
+    fn absorb_inclusions(&self, lazy_definitions: &HashMap<String, &TBoxAxiom>) -> (Vec<TBoxAxiom>, Vec<TBoxAxiom>) {
+        let inclusion = |lhs: &dyn Concept, rhs: &dyn Concept| {
+            TBoxAxiom { axiom_type: TBoxAxiomType::Inclusion, lhs: lhs.clone_box(), rhs: rhs.clone_box() }
+        };
+        let inclusions = self.sorted_axioms()
+            .into_iter()
+            .flat_map(|a| match a.axiom_type {
+                TBoxAxiomType::Inclusion => vec![inclusion(&*a.lhs, &*a.rhs)],
+                TBoxAxiomType::Definition if lazy_definitions.values().any(|d| d == &a) => vec![],
+                TBoxAxiomType::Definition => vec![inclusion(&*a.lhs, &*a.rhs), inclusion(&*a.rhs, &*a.lhs)]
+            })
+            .collect::<Vec<TBoxAxiom>>();
+        let mut absorbed = vec![];
+        let mut remaining = vec![];
+
+        for gci in inclusions {
+            match absorb_inclusion(&*gci.lhs, &*gci.rhs, lazy_definitions) {
+                Some(rule) => absorbed.push(rule),
+                None => remaining.push(gci)
             }
         }
+
+        (absorbed, remaining)
     }
 
-    pub fn aggregate_inclusions(&self) -> Option<ConjunctionConcept> {
-        info!("Aggregating GCIs into a single one...");
-        let inclusions = self.axioms.clone().into_iter()
+    pub fn told_inclusions(&self) -> Vec<(String, Box<dyn Concept>)> {
+        // The axioms, which tell what a concept name implies: "A -> C", "A == C" and "C == A"
+        self.axioms.iter()
+            .flat_map(|a| {
+                let lhs = a.lhs.downcast_ref::<AtomicConcept>().map(|c| (c.name.clone(), a.rhs.clone()));
+                let rhs = match a.axiom_type {
+                    TBoxAxiomType::Definition => a.rhs.downcast_ref::<AtomicConcept>().map(|c| (c.name.clone(), a.lhs.clone())),
+                    TBoxAxiomType::Inclusion => None
+                };
+
+                lhs.into_iter().chain(rhs)
+            })
+            .collect()
+    }
+
+    pub fn take_inclusions(&mut self) -> Vec<TBoxAxiom> {
+        // Removes all the GCIs from the TBox and returns them
+        // (this is useful when the TBox file contains the subsumptions to check)
+        let inclusions = self.axioms.iter()
             .filter(|a| a.axiom_type == TBoxAxiomType::Inclusion)
-            .collect::<Vec<Box<TBoxAxiom>>>();
+            .map(|a| *a.clone())
+            .collect::<Vec<TBoxAxiom>>();
+        self.axioms.retain(|a| a.axiom_type != TBoxAxiomType::Inclusion);
 
-        if inclusions.is_empty() {
-            return None;
-        }
+        inclusions
+    }
+
+    fn aggregate_inclusions(&self, inclusions: Vec<TBoxAxiom>) -> Option<Box<dyn Concept>> {
+        debug!("Aggregating GCIs into a single one...");
+
+        // Functional relations are just implicit "<= 1 r __TOP__" restrictions on every individual
+        let functional_restrictions = self.rbox.functional_relations()
+            .into_iter()
+            .map(|r| Box::new(AtMostConcept {
+                amount: 1,
+                relation: r,
+                subconcept: Box::new(TopConcept)
+            }) as Box<dyn Concept>);
 
         let subconcepts = inclusions
             .into_iter()
             .map(|ta| {DisjunctionConcept {subconcepts: vec![ta.lhs.negate().convert_to_nnf(), ta.rhs]}})
             .map(|a| Box::new(a) as Box<dyn Concept>)
+            .chain(functional_restrictions)
             .collect::<Vec<Box<dyn Concept>>>();
 
-        Some(ConjunctionConcept {subconcepts: subconcepts})
+        // Trivial GCIs (e.g. "A -> __TOP__") are simplified away here
+        let super_concept = ConjunctionConcept {subconcepts}.convert_to_nnf();
+
+        match super_concept.concept_type() {
+            ConceptType::Top => None,
+            _ => Some(super_concept)
+        }
     }
 }
 
+/// Lazy unfolding rules "A -> C" and "not A -> not C" for each definition "A == C":
+/// the tableau adds the rhs to a label only when the lhs appears there,
+/// so the definitions are never substituted into each other.
+fn definition_rules(lazy_definitions: &HashMap<String, &TBoxAxiom>) -> Vec<TBoxAxiom> {
+    let mut definitions = lazy_definitions.iter().collect::<Vec<(&String, &&TBoxAxiom)>>();
+    definitions.sort_by_key(|(name, _)| *name);
+
+    definitions
+        .into_iter()
+        .flat_map(|(_, d)| vec![
+            TBoxAxiom { axiom_type: TBoxAxiomType::Inclusion, lhs: d.lhs.clone(), rhs: d.rhs.clone() },
+            TBoxAxiom {
+                axiom_type: TBoxAxiomType::Inclusion,
+                lhs: d.lhs.negate(),
+                rhs: d.rhs.negate().convert_to_nnf()
+            }
+        ])
+        .collect()
+}
+
+fn find_definition_path(from: &str, to: &str, definitions: &HashMap<String, &TBoxAxiom>,
+                        visited: &mut HashSet<String>) -> Option<Vec<String>> {
+    // The names, through which `from` is defined, up to `to` (depth-first, so the path is not the shortest one)
+    let mut names = extract_atomic_names(&*definitions.get(from)?.rhs).into_iter().collect::<Vec<String>>();
+    names.sort();
+
+    for name in names {
+        if name == to {
+            return Some(vec![name]);
+        }
+
+        if visited.insert(name.clone()) {
+            if let Some(mut path) = find_definition_path(&name, to, definitions, visited) {
+                path.insert(0, name);
+                return Some(path);
+            }
+        }
+    }
+
+    None
+}
+
+fn absorb_inclusion(lhs: &dyn Concept, rhs: &dyn Concept, lazy_definitions: &HashMap<String, &TBoxAxiom>) -> Option<TBoxAxiom> {
+    // "A -> D" and "and (A C) -> D" are absorbed into the unfolding rule "A -> or ((not C) D)".
+    // A should not have a definition: lazy unfolding of "A == E" would not see, that E implies D now
+    let mut conjuncts = match lhs.downcast_ref::<ConjunctionConcept>() {
+        Some(conjunction) => conjunction.subconcepts.clone(),
+        None => vec![lhs.clone_box()]
+    };
+    let position = conjuncts.iter().position(|c| {
+        c.downcast_ref::<AtomicConcept>().is_some_and(|a| !lazy_definitions.contains_key(&a.name))
+    })?;
+    let name = conjuncts.remove(position);
+    let rhs = match conjuncts.is_empty() {
+        true => rhs.clone_box(),
+        false => DisjunctionConcept {
+            subconcepts: conjuncts.into_iter().map(|c| c.negate()).chain(Some(rhs.clone_box())).collect()
+        }.convert_to_nnf()
+    };
+
+    Some(TBoxAxiom { axiom_type: TBoxAxiomType::Inclusion, lhs: name, rhs })
+}
+
 impl fmt::Display for TBox {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        write!(fmt, "TBox:\n  - {}", self.axioms.iter()
-            .map(|a| a.to_string()).collect::<Vec<String>>().join("\n  - "))
+        write!(fmt, "TBox:\n  - {}\n{}", self.axioms.iter()
+            .map(|a| a.to_string()).collect::<Vec<String>>().join("\n  - "), self.rbox)
     }
 }
 
+/// The TBox in the form, which the tableau works with
+#[derive(Debug, Clone)]
+pub struct Absorption {
+    // Lazy unfolding rules of the definitions and of the absorbed GCIs
+    pub unfolding_rules: Vec<TBoxAxiom>,
+    // The GCIs, which were not absorbed, as a single concept for every individual
+    pub super_concept: Option<Box<dyn Concept>>,
+    pub num_absorbed: usize,
+    pub num_inclusions: usize
+}
+
 #[derive(Debug, Clone, Hash, Eq, PartialEq)]
 pub enum TBoxAxiomType { Definition, Inclusion }
 
@@ -170,7 +407,7 @@ pub struct TBoxAxiom {
 impl fmt::Display for TBoxAxiom {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         let delimiter = if self.axiom_type == TBoxAxiomType::Definition {"=="} else {"->"};
-        write!(fmt, "{} {} {}", self.lhs.to_string(), delimiter, self.rhs.to_string())
+        write!(fmt, "{} {} {}", self.lhs, delimiter, self.rhs)
     }
 }
 
@@ -187,3 +424,53 @@ impl hash::Hash for TBoxAxiom {
         self.to_string().hash(hasher);
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_subsumption_queries() {
+        let queries = parse_subsumption_queries("# Queries\nMother -> Parent\n\nand (A B) -> A").unwrap();
+
+        assert_eq!(queries.iter().map(|q| q.to_string()).collect::<Vec<String>>(), vec!["Mother -> Parent", "and (A) (B) -> A"]);
+        assert_eq!(parse_subsumption_queries("A -> B\nMother == Parent").unwrap_err()[0].line, 2);
+        assert!(parse_subsumption_queries("A -> some").is_err());
+    }
+
+    #[test]
+    fn test_absorption() {
+        let tbox = parse_tbox("
+            A -> some r B
+            and (C D) -> E
+            F == or (X Y)
+            and (F (some r G)) -> H
+            some r B -> C
+        ").unwrap();
+        let (absorbed, remaining) = tbox.absorb_inclusions(&tbox.lazy_definitions());
+        let mut rules = absorbed.iter().map(|a| a.to_string()).collect::<Vec<String>>();
+        rules.sort();
+
+        // F is unfolded lazily, so the GCI cannot be absorbed into it
+        assert_eq!(rules, vec!["A -> some r (B)", "C -> or (not D) (E)"]);
+        assert_eq!(remaining.len(), 2);
+        assert_eq!(tbox.absorb().unfolding_rules.len(), 4);
+    }
+
+    #[test]
+    fn test_definition_cycles() {
+        let tbox = parse_tbox("A == some r B\nB == and (C (only r D))\nD == or (A E)\nE == not C").unwrap();
+
+        assert_eq!(tbox.find_definition_cycle(), Some(vec!["A", "B", "D", "A"].into_iter().map(String::from).collect()));
+        assert!(tbox.check_definitions().unwrap_err().to_string().contains("A -> B -> D -> A"));
+
+        // The cyclic definitions become GCIs: all of them but "some r B -> A" and "or (A E) -> D" are absorbed
+        let absorption = tbox.absorb();
+        assert_eq!((absorption.num_absorbed, absorption.num_inclusions), (4, 6));
+        assert_eq!(absorption.unfolding_rules.len(), 6);
+
+        assert!(parse_tbox("A == some r A").unwrap().find_definition_cycle().is_some());
+        assert!(parse_tbox("A == some r B\nB == C").unwrap().check_definitions().is_ok());
+    }
+}
@@ -0,0 +1,152 @@
+/*
+    Told subsumers of a concept name are the names, which the TBox axioms state it implies directly:
+    "A -> and (B (not C) (some r D))" and "A == and (B ...)" make B a told subsumer of A and C a told disjoint name of A.
+    The told subsumers are closed transitively, so A ⊑ B is certain without the tableau, if B is among them.
+    If A is told to imply ¬N and B is told to imply N (or vice versa), A ⊓ B is unsatisfiable,
+    so A ⊑ B holds iff A itself is unsatisfiable (which is one tableau run for all the B's).
+*/
+use std::collections::{HashSet, HashMap};
+
+use concept::{Concept, AtomicConcept, NotConcept, ConjunctionConcept};
+use tbox::TBox;
+
+
+#[derive(Debug, Clone, Default)]
+pub struct ToldSubsumers {
+    // Every name here is a subsumer of itself
+    subsumers: HashMap<String, HashSet<String>>,
+    // The names, which negations are implied by the told subsumers
+    disjoint_names: HashMap<String, HashSet<String>>
+}
+
+impl ToldSubsumers {
+    pub fn new(tbox: &TBox) -> ToldSubsumers {
+        let mut direct_subsumers: HashMap<String, HashSet<String>> = HashMap::new();
+        let mut direct_disjoint_names: HashMap<String, HashSet<String>> = HashMap::new();
+
+        for (name, concept) in tbox.told_inclusions() {
+            for conjunct in conjuncts(&*concept) {
+                if let Some(atomic) = conjunct.downcast_ref::<AtomicConcept>() {
+                    direct_subsumers.entry(name.clone()).or_default().insert(atomic.name.clone());
+                } else if let Some(atomic) = conjunct.downcast_ref::<NotConcept>().and_then(|c| c.subconcept.downcast_ref::<AtomicConcept>()) {
+                    direct_disjoint_names.entry(name.clone()).or_default().insert(atomic.name.clone());
+                }
+            }
+        }
+
+        let names = direct_subsumers.keys().chain(direct_disjoint_names.keys()).cloned().collect::<HashSet<String>>();
+        let mut told_subsumers = ToldSubsumers::default();
+
+        for name in names {
+            let subsumers = find_reachable_names(&name, &direct_subsumers);
+            let disjoint_names = subsumers.iter()
+                .filter_map(|s| direct_disjoint_names.get(s))
+                .flatten()
+                .cloned()
+                .collect::<HashSet<String>>();
+
+            told_subsumers.disjoint_names.insert(name.clone(), disjoint_names);
+            told_subsumers.subsumers.insert(name, subsumers);
+        }
+
+        told_subsumers
+    }
+
+    pub fn is_subsumed(&self, sub: &str, sup: &str) -> bool {
+        sub == sup || self.subsumers.get(sub).is_some_and(|s| s.contains(sup))
+    }
+
+    pub fn are_disjoint(&self, lhs: &str, rhs: &str) -> bool {
+        self.implies_negation(lhs, rhs) || self.implies_negation(rhs, lhs)
+    }
+
+    /// Puts the told subsumers of every name before it, otherwise the order is kept.
+    /// A strict told subsumer of a name has fewer told subsumers than the name,
+    /// so sorting the subsumers of a name by their numbers orders them top-down.
+    pub fn sort_by_subsumers(&self, names: &[String]) -> Vec<String> {
+        let name_set = names.iter().collect::<HashSet<&String>>();
+        let mut visited = HashSet::new();
+        let mut sorted_names = vec![];
+
+        for name in names {
+            let mut subsumers = match self.subsumers.get(name) {
+                Some(subsumers) => subsumers.iter().filter(|s| name_set.contains(s)).collect::<Vec<&String>>(),
+                None => vec![name]
+            };
+            subsumers.sort_by_key(|&s| (self.subsumers.get(s).map_or(1, |ss| ss.len()), s != name, s.clone()));
+
+            for s in subsumers {
+                if visited.insert(s.clone()) {
+                    sorted_names.push(s.clone());
+                }
+            }
+        }
+
+        sorted_names
+    }
+
+    fn implies_negation(&self, lhs: &str, rhs: &str) -> bool {
+        // I.e. lhs implies ¬N for some told subsumer N of rhs
+        match self.disjoint_names.get(lhs) {
+            Some(disjoint_names) => disjoint_names.contains(rhs)
+                || self.subsumers.get(rhs).is_some_and(|s| !s.is_disjoint(disjoint_names)),
+            None => false
+        }
+    }
+}
+
+
+fn conjuncts(concept: &dyn Concept) -> Vec<&dyn Concept> {
+    match concept.downcast_ref::<ConjunctionConcept>() {
+        Some(conjunction) => conjunction.subconcepts.iter().flat_map(|c| conjuncts(&**c)).collect(),
+        None => vec![concept]
+    }
+}
+
+fn find_reachable_names(name: &str, edges: &HashMap<String, HashSet<String>>) -> HashSet<String> {
+    let mut reachable = HashSet::new();
+    let mut stack = vec![name.to_string()];
+
+    while let Some(x) = stack.pop() {
+        if let Some(ys) = edges.get(&x) {
+            stack.extend(ys.iter().filter(|y| !reachable.contains(*y)).cloned());
+        }
+
+        reachable.insert(x);
+    }
+
+    reachable
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tbox::parse_tbox;
+
+    #[test]
+    fn test_told_subsumers() {
+        let tbox = parse_tbox("
+            Mother == and (Parent Female)
+            Parent == and (Human (some hasChild Human))
+            Female -> not Male
+            Father -> and (Parent Male)
+            Person == Human
+            Parent -> or (Mother Father)
+        ").unwrap();
+        let told = ToldSubsumers::new(&tbox);
+
+        assert!(told.is_subsumed("Mother", "Human"));
+        assert!(told.is_subsumed("Mother", "Mother"));
+        assert!(told.is_subsumed("Human", "Person"));
+        assert!(told.is_subsumed("Father", "Person"));
+        assert!(!told.is_subsumed("Parent", "Mother"));
+        assert!(!told.is_subsumed("Human", "Parent"));
+
+        assert!(told.are_disjoint("Mother", "Male"));
+        assert!(told.are_disjoint("Father", "Mother"));
+        assert!(told.are_disjoint("Female", "Father"));
+        assert!(!told.are_disjoint("Human", "Female"));
+        assert!(!told.are_disjoint("Parent", "Male"));
+    }
+}